@@ -1,5 +1,11 @@
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::clock::Clock;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::sysvar::slot_hashes;
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{self, Mint, TokenAccount, TokenInterface, TransferChecked};
+use mpl_bubblegum::instructions::MintToCollectionV1CpiBuilder;
+use mpl_bubblegum::types::{Collection, MetadataArgs, TokenProgramVersion, TokenStandard};
 use std::collections::HashMap;
 
 declare_id!("C2CdtqX8Xb3Jask61G8g3xFzn6bmXcQ623YmcCeyFUPk");
@@ -14,6 +20,220 @@ pub mod solana_contract {
         Ok(())
     }
 
+    pub fn initialize_protocol_config(
+        ctx: Context<InitializeProtocolConfig>,
+        treasury: Pubkey,
+        rake_bps: u16,
+        claim_window_secs: u32,
+        sweep_to_season: bool,
+        referral_share_bps: u16,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        
+        require!(rake_bps <= ProtocolConfig::MAX_RAKE_BPS, ErrorCode::InvalidRake);
+        require!(claim_window_secs > 0, ErrorCode::InvalidClaimWindow);
+        require!(referral_share_bps <= 10_000, ErrorCode::InvalidReferral);
+        
+        config.admin = ctx.accounts.admin.key();
+        config.treasury = treasury;
+        config.rake_bps = rake_bps;
+        config.claim_window_secs = claim_window_secs;
+        config.sweep_to_season = sweep_to_season;
+        config.referral_share_bps = referral_share_bps;
+        
+        emit!(ProtocolConfigUpdated {
+            admin: config.admin,
+            treasury: config.treasury,
+            rake_bps: config.rake_bps,
+            claim_window_secs: config.claim_window_secs,
+            sweep_to_season: config.sweep_to_season,
+            referral_share_bps: config.referral_share_bps,
+        });
+        
+        Ok(())
+    }
+
+    pub fn update_protocol_config(
+        ctx: Context<UpdateProtocolConfig>,
+        admin: Option<Pubkey>,
+        treasury: Option<Pubkey>,
+        rake_bps: Option<u16>,
+        claim_window_secs: Option<u32>,
+        sweep_to_season: Option<bool>,
+        referral_share_bps: Option<u16>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        
+        require!(config.admin == ctx.accounts.admin.key(), ErrorCode::NotAdmin);
+        
+        if let Some(admin) = admin {
+            config.admin = admin;
+        }
+        if let Some(treasury) = treasury {
+            config.treasury = treasury;
+        }
+        if let Some(rake_bps) = rake_bps {
+            require!(rake_bps <= ProtocolConfig::MAX_RAKE_BPS, ErrorCode::InvalidRake);
+            config.rake_bps = rake_bps;
+        }
+        if let Some(claim_window_secs) = claim_window_secs {
+            require!(claim_window_secs > 0, ErrorCode::InvalidClaimWindow);
+            config.claim_window_secs = claim_window_secs;
+        }
+        if let Some(sweep_to_season) = sweep_to_season {
+            config.sweep_to_season = sweep_to_season;
+        }
+        if let Some(referral_share_bps) = referral_share_bps {
+            require!(referral_share_bps <= 10_000, ErrorCode::InvalidReferral);
+            config.referral_share_bps = referral_share_bps;
+        }
+        
+        emit!(ProtocolConfigUpdated {
+            admin: config.admin,
+            treasury: config.treasury,
+            rake_bps: config.rake_bps,
+            claim_window_secs: config.claim_window_secs,
+            sweep_to_season: config.sweep_to_season,
+            referral_share_bps: config.referral_share_bps,
+        });
+        
+        Ok(())
+    }
+
+    pub fn create_referral_account(ctx: Context<CreateReferralAccount>) -> Result<()> {
+        let referral = &mut ctx.accounts.referral;
+        
+        // Only wallets that have played can refer others; see `join_game`
+        require!(ctx.accounts.profile.games_joined > 0, ErrorCode::InvalidReferral);
+        
+        referral.referrer = ctx.accounts.referrer.key();
+        referral.referred_count = 0;
+        referral.pending = 0;
+        referral.total_earned = 0;
+        
+        emit!(ReferralAccountCreated {
+            referrer: referral.referrer,
+        });
+        
+        Ok(())
+    }
+
+    pub fn claim_referral(ctx: Context<ClaimReferral>) -> Result<()> {
+        let referral = &mut ctx.accounts.referral;
+        
+        let amount = referral.pending;
+        require!(amount > 0, ErrorCode::NoReferralRewards);
+        referral.pending = 0;
+        
+        **referral.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.referrer.to_account_info().try_borrow_mut_lamports()? += amount;
+        
+        emit!(ReferralClaimed {
+            referrer: referral.referrer,
+            amount,
+        });
+        
+        Ok(())
+    }
+
+    pub fn configure_badges(
+        ctx: Context<ConfigureBadges>,
+        merkle_tree: Pubkey,
+        collection_mint: Pubkey,
+        uri_base: String,
+    ) -> Result<()> {
+        let badges = &mut ctx.accounts.badges;
+        
+        require!(ctx.accounts.config.admin == ctx.accounts.admin.key(), ErrorCode::NotAdmin);
+        require!(uri_base.len() <= BadgeConfig::MAX_URI_BASE_LEN, ErrorCode::InvalidBadgeConfig);
+        
+        badges.merkle_tree = merkle_tree;
+        badges.collection_mint = collection_mint;
+        badges.uri_base = uri_base.clone();
+        
+        emit!(BadgesConfigured {
+            merkle_tree,
+            collection_mint,
+            uri_base,
+        });
+        
+        Ok(())
+    }
+
+    pub fn mint_achievement(ctx: Context<MintAchievement>, achievement: Achievement) -> Result<()> {
+        let wallet = ctx.accounts.wallet.key();
+        let clock = Clock::get()?;
+        
+        require!(achievement.is_earned(&ctx.accounts.profile), ErrorCode::AchievementNotEarned);
+        
+        let receipt = &mut ctx.accounts.receipt;
+        receipt.wallet = wallet;
+        receipt.achievement = achievement;
+        receipt.minted_at = clock.unix_timestamp;
+        
+        // The badge authority PDA is both the tree delegate and the collection's update authority
+        let metadata = MetadataArgs {
+            name: achievement.name().to_string(),
+            symbol: BadgeConfig::SYMBOL.to_string(),
+            uri: format!("{}/{}.json", ctx.accounts.badges.uri_base, achievement.slug()),
+            seller_fee_basis_points: 0,
+            primary_sale_happened: true,
+            is_mutable: false,
+            edition_nonce: None,
+            token_standard: Some(TokenStandard::NonFungible),
+            collection: Some(Collection {
+                verified: false,
+                key: ctx.accounts.badges.collection_mint,
+            }),
+            uses: None,
+            token_program_version: TokenProgramVersion::Original,
+            creators: Vec::new(),
+        };
+        let bubblegum_program = ctx.accounts.bubblegum_program.to_account_info();
+        let wallet_info = ctx.accounts.wallet.to_account_info();
+        let badge_authority = ctx.accounts.badge_authority.to_account_info();
+        let signer_seeds: &[&[&[u8]]] = &[&[b"badge_authority", &[ctx.bumps.badge_authority]]];
+        MintToCollectionV1CpiBuilder::new(&bubblegum_program)
+            .tree_config(&ctx.accounts.tree_config.to_account_info())
+            .leaf_owner(&wallet_info)
+            .leaf_delegate(&wallet_info)
+            .merkle_tree(&ctx.accounts.merkle_tree.to_account_info())
+            .payer(&wallet_info)
+            .tree_creator_or_delegate(&badge_authority)
+            .collection_authority(&badge_authority)
+            .collection_authority_record_pda(Some(&bubblegum_program))
+            .collection_mint(&ctx.accounts.collection_mint.to_account_info())
+            .collection_metadata(&ctx.accounts.collection_metadata.to_account_info())
+            .collection_edition(&ctx.accounts.collection_edition.to_account_info())
+            .bubblegum_signer(&ctx.accounts.bubblegum_signer.to_account_info())
+            .log_wrapper(&ctx.accounts.log_wrapper.to_account_info())
+            .compression_program(&ctx.accounts.compression_program.to_account_info())
+            .token_metadata_program(&ctx.accounts.token_metadata_program.to_account_info())
+            .system_program(&ctx.accounts.system_program.to_account_info())
+            .metadata(metadata)
+            .invoke_signed(signer_seeds)?;
+        
+        emit!(AchievementMinted {
+            wallet,
+            achievement,
+        });
+        
+        Ok(())
+    }
+
+    pub fn create_profile(ctx: Context<CreateProfile>) -> Result<()> {
+        let profile = &mut ctx.accounts.profile;
+        profile.wallet = ctx.accounts.wallet.key();
+        profile.rating = PlayerProfile::STARTING_RATING;
+        
+        emit!(ProfileCreated {
+            wallet: profile.wallet,
+            rating: profile.rating,
+        });
+        
+        Ok(())
+    }
+
     pub fn initialize_game(
         ctx: Context<InitializeGame>,
         name: String,
@@ -41,9 +261,33 @@ pub mod solana_contract {
         game.roles = Vec::new();
         game.votes = Vec::new();
         game.eliminated_players = Vec::new();
+        game.night_actions = Vec::new();
+        game.seances = Vec::new();
+        game.mafia_target = None;
+        game.on_trial = None;
         game.current_phase = GamePhase::Lobby;
         game.day_count = 0;
         game.winner = None;
+        game.mode = GameMode::Classic;
+        game.settings = GameSettings::default();
+        game.ratings_settled = false;
+        game.season = None;
+        game.season_share_bps = 0;
+        // The rake is fixed when the game is created so later config changes don't touch it
+        game.rake_bps = ctx.accounts.config.rake_bps;
+        game.claim_window_secs = ctx.accounts.config.claim_window_secs;
+        game.referral_share_bps = ctx.accounts.config.referral_share_bps;
+        game.claim_deadline = 0;
+        game.fees_collected = false;
+        game.pot = 0;
+        game.sponsor_pool = 0;
+        
+        // Token games keep their pot in a vault owned by the escrow PDA
+        game.mint = ctx.accounts.mint.as_ref().map(|mint| mint.key());
+        game.escrow_bump = ctx.bumps.escrow;
+        if game.mint.is_some() {
+            require!(ctx.accounts.vault.is_some(), ErrorCode::MissingTokenAccounts);
+        }
         
         emit!(GameCreated {
             game_id: game.game_id.clone(),
@@ -51,625 +295,4294 @@ pub mod solana_contract {
             max_players,
             name,
             entry_fee,
+            mint: game.mint,
             is_public,
         });
         
         Ok(())
     }
 
-    pub fn join_game(ctx: Context<JoinGame>) -> Result<()> {
+    pub fn join_game(ctx: Context<JoinGame>, referrer: Option<Pubkey>) -> Result<()> {
         let player = ctx.accounts.player.key();
-        let game_key = ctx.accounts.game.key();
-        let game_account_info = ctx.accounts.game.to_account_info();
-        let game = &mut ctx.accounts.game;
         
-        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotJoinable);
-        require!(game.players.len() <= game.max_players as usize, ErrorCode::GameFull);
-        require!(!game.players.iter().any(|p| p.address == player), ErrorCode::AlreadyJoined);
+        {
+            let game = &ctx.accounts.game;
+            require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotJoinable);
+            require!(ctx.accounts.prediction_bet.data_is_empty(), ErrorCode::BettorCannotPlay);
+            require!(game.players.len() <= game.max_players as usize, ErrorCode::GameFull);
+            require!(!game.players.iter().any(|p| p.address == player), ErrorCode::AlreadyJoined);
+        }
         
         // Transfer entry fee
-        if game.entry_fee > 0 {
-            let ix = anchor_lang::solana_program::system_instruction::transfer(
-                &ctx.accounts.player.key(),
-                &game_key,
-                game.entry_fee,
-            );
-            anchor_lang::solana_program::program::invoke(
-                &ix,
-                &[
-                    ctx.accounts.player.to_account_info(),
-                    game_account_info.clone(),
-                ],
-            )?;
+        let received = if ctx.accounts.game.entry_fee > 0 {
+            deposit_to_pot(
+                &ctx.accounts.game,
+                &ctx.accounts.token_pot,
+                &ctx.accounts.player,
+                &ctx.accounts.system_program,
+                ctx.accounts.game.entry_fee,
+            )?
+        } else {
+            0
+        };
+        
+        let game = &mut ctx.accounts.game;
+        let profile = &mut ctx.accounts.profile;
+        game.pot += received;
+        
+        // Profiles are created the first time a wallet joins a game
+        if profile.wallet == Pubkey::default() {
+            profile.wallet = player;
+            profile.rating = PlayerProfile::STARTING_RATING;
+            
+            emit!(ProfileCreated {
+                wallet: player,
+                rating: profile.rating,
+            });
         }
         
-        game.players.push(Player {
-            address: player,
-            is_alive: true,
-            role: Role::Unknown,
-            vote_target: None,
-            joined_at: Clock::get()?.unix_timestamp,
-        });
+        // Referrals are only taken on a wallet's first join, and referrers must have played
+        // before, so every referrer joined earlier than the players they refer and chains
+        // can never loop back on themselves. Referral rewards are paid in SOL, so a
+        // referral can't be taken in a token game.
+        if let Some(referrer) = referrer {
+            require!(game.mint.is_none(), ErrorCode::InvalidReferral);
+            require_keys_neq!(referrer, player, ErrorCode::SelfReferral);
+            require!(profile.games_joined == 0 && profile.referrer.is_none(), ErrorCode::InvalidReferral);
+            let referral = ctx.accounts.referral.as_mut().ok_or(ErrorCode::InvalidReferral)?;
+            require_keys_eq!(referral.referrer, referrer, ErrorCode::InvalidReferral);
+            
+            referral.referred_count += 1;
+            profile.referrer = Some(referrer);
+            
+            emit!(PlayerReferred {
+                player,
+                referrer,
+            });
+        }
+        profile.games_joined += 1;
+        
+        let mut entry = Player::new(player, Clock::get()?.unix_timestamp);
+        entry.referrer = profile.referrer;
+        game.players.push(entry);
         
         emit!(PlayerJoined {
             game_id: game.game_id.clone(),
-            player: player,
+            player,
             player_count: game.players.len() as u8,
         });
         
         Ok(())
     }
 
-    pub fn start_game(ctx: Context<StartGame>) -> Result<()> {
+    pub fn leave_game(ctx: Context<LeaveGame>) -> Result<()> {
+        let player = ctx.accounts.player.key();
+        let game = &ctx.accounts.game;
+        
+        // Players of a cancelled game leave the same way to get their entry fee back
+        require!(
+            game.state == GameState::WaitingForPlayers || game.state == GameState::Cancelled,
+            ErrorCode::GameNotJoinable
+        );
+        let index = player_index(game, &player)?;
+        
+        // Everyone paid the same fee, so the leaver takes back an equal share of what the pot received
+        let refund = game.pot / game.players.len() as u64;
+        let recipient = payout_destination(game, &ctx.accounts.token_pot, &ctx.accounts.player.to_account_info())?;
+        pay_from_pot(game, &ctx.accounts.token_pot, &recipient, refund)?;
+        
         let game = &mut ctx.accounts.game;
-        let clock = Clock::get()?;
+        game.players.remove(index);
+        game.pot -= refund;
         
-        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
-        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotStartable);
-        require!(game.players.len() >= 4, ErrorCode::NotEnoughPlayers);
+        emit!(PlayerLeft {
+            game_id: game.game_id.clone(),
+            player,
+            refund,
+        });
         
-        // Assign roles randomly (simplified for demo)
-        let mafia_count = std::cmp::max(1, game.players.len() / 4);
+        Ok(())
+    }
+
+    pub fn sponsor_game(ctx: Context<SponsorGame>, amount: u64) -> Result<()> {
+        let sponsor = ctx.accounts.sponsor.key();
+        let game = &ctx.accounts.game;
         
-        for (i, player) in game.players.iter_mut().enumerate() {
-            if i < mafia_count {
-                player.role = Role::Mafia;
-            } else if i == mafia_count {
-                player.role = Role::Detective;
-            } else if i == mafia_count + 1 {
-                player.role = Role::Doctor;
-            } else {
-                player.role = Role::Civilian;
-            }
-        }
+        require!(
+            game.state == GameState::WaitingForPlayers || game.state == GameState::Active,
+            ErrorCode::GameNotActive
+        );
+        require!(!game.creator_override, ErrorCode::CreatorOverride);
+        require!(amount > 0, ErrorCode::InvalidSponsorship);
         
-        game.state = GameState::Active;
-        game.current_phase = GamePhase::Day;
-        game.phase_start_time = clock.unix_timestamp;
-        game.day_count = 1;
+        let received = deposit_to_pot(
+            game,
+            &ctx.accounts.token_pot,
+            &ctx.accounts.sponsor,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+        
+        let game = &mut ctx.accounts.game;
+        let sponsorship = &mut ctx.accounts.sponsorship;
+        game.sponsor_pool += received;
+        sponsorship.game = game.key();
+        sponsorship.sponsor = sponsor;
+        sponsorship.amount += received;
         
-        emit!(GameStarted {
+        emit!(GameSponsored {
             game_id: game.game_id.clone(),
-            player_count: game.players.len() as u8,
-            day_count: game.day_count,
+            sponsor,
+            amount: received,
+            sponsor_pool: game.sponsor_pool,
         });
         
         Ok(())
     }
 
-    pub fn cast_vote(ctx: Context<CastVote>, target_player: Pubkey) -> Result<()> {
+    pub fn cancel_game(ctx: Context<CancelGame>) -> Result<()> {
         let game = &mut ctx.accounts.game;
-        let voter = ctx.accounts.voter.key();
         
-        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
-        require!(game.current_phase == GamePhase::Day, ErrorCode::NotVotingPhase);
+        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
+        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotStartable);
         
-        let voter_index = game.players.iter().position(|p| p.address == voter)
-            .ok_or(ErrorCode::PlayerNotInGame)?;
-        let target_index = game.players.iter().position(|p| p.address == target_player)
-            .ok_or(ErrorCode::InvalidTarget)?;
+        game.state = GameState::Cancelled;
         
-        require!(game.players[voter_index].is_alive, ErrorCode::PlayerDead);
-        require!(game.players[target_index].is_alive, ErrorCode::InvalidTarget);
+        emit!(GameCancelled {
+            game_id: game.game_id.clone(),
+            player_count: game.players.len() as u8,
+            sponsor_pool: game.sponsor_pool,
+        });
         
-        // Remove previous vote if exists
-        game.votes.retain(|v| v.voter != voter);
+        Ok(())
+    }
+
+    pub fn refund_sponsorship(ctx: Context<RefundSponsorship>) -> Result<()> {
+        let game = &ctx.accounts.game;
+        let sponsorship = &ctx.accounts.sponsorship;
         
-        // Add new vote
-        game.votes.push(Vote {
-            voter,
-            target: target_player,
-            timestamp: Clock::get()?.unix_timestamp,
-        });
+        require!(game.state == GameState::Cancelled, ErrorCode::GameNotCancelled);
+        require!(!sponsorship.refunded, ErrorCode::AlreadyPaid);
         
-        game.players[voter_index].vote_target = Some(target_player);
+        // Each sponsor gets back their share of the bonus, net of any token transfer fee on the way in
+        let refund = sponsorship.amount;
+        let recipient = payout_destination(game, &ctx.accounts.token_pot, &ctx.accounts.sponsor.to_account_info())?;
+        pay_from_pot(game, &ctx.accounts.token_pot, &recipient, refund)?;
         
-        emit!(VoteCast {
+        let game = &mut ctx.accounts.game;
+        game.sponsor_pool -= refund;
+        ctx.accounts.sponsorship.refunded = true;
+        
+        emit!(SponsorshipRefunded {
             game_id: game.game_id.clone(),
-            voter,
-            target: target_player,
-            total_votes: game.votes.len() as u8,
+            sponsor: ctx.accounts.sponsor.key(),
+            amount: refund,
         });
         
         Ok(())
     }
 
-    pub fn advance_phase(ctx: Context<AdvancePhase>) -> Result<()> {
+    pub fn configure_roles(ctx: Context<ConfigureRoles>, roles: Vec<Role>) -> Result<()> {
         let game = &mut ctx.accounts.game;
-        let clock = Clock::get()?;
         
-        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
+        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotStartable);
+        require!(roles.len() <= 20, ErrorCode::TooManyPlayers);
+        require!(!roles.contains(&Role::Unknown), ErrorCode::InvalidRoleDeck);
+        require!(roles.iter().any(|r| r.is_mafia()), ErrorCode::InvalidRoleDeck);
+        // Night action storage has room for a single Witch's second potion
+        require!(roles.iter().filter(|r| **r == Role::Witch).count() <= 1, ErrorCode::InvalidRoleDeck);
         
-        match game.current_phase {
-            GamePhase::Day => {
-                // Process day votes and eliminate player
-                let elimination_result = process_day_votes(game)?;
-                if let Some(eliminated) = elimination_result {
-                    emit!(PlayerEliminated {
-                        game_id: game.game_id.clone(),
-                        player: eliminated,
-                        phase: game.current_phase,
-                        day_count: game.day_count,
-                    });
-                }
-                
-                game.current_phase = GamePhase::Night;
-            },
-            GamePhase::Night => {
-                // Process night actions (simplified)
-                game.current_phase = GamePhase::Day;
-                game.day_count += 1;
-            },
-            _ => return Err(ErrorCode::InvalidPhase.into()),
-        }
+        game.roles = roles;
         
-        game.phase_start_time = clock.unix_timestamp;
-        game.votes.clear();
+        emit!(RolesConfigured {
+            game_id: game.game_id.clone(),
+            roles: game.roles.clone(),
+        });
         
-        // Clear vote targets
-        for player in &mut game.players {
-            player.vote_target = None;
-        }
+        Ok(())
+    }
+
+    pub fn set_game_mode(ctx: Context<ConfigureGame>, mode: GameMode) -> Result<()> {
+        let game = &mut ctx.accounts.game;
         
-        // Check win conditions
-        let win_result = check_win_condition(game)?;
-        if let Some(winner) = win_result {
-            game.state = GameState::Finished;
-            game.winner = Some(winner.clone());
-            
-            emit!(GameEnded {
-                game_id: game.game_id.clone(),
-                winner,
-                day_count: game.day_count,
-            });
-        }
+        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
+        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotStartable);
+        
+        // Switching modes starts from that mode's preset rules
+        game.mode = mode;
+        game.settings = match mode {
+            GameMode::Classic => GameSettings::default(),
+            GameMode::Speed => GameSettings::speed(),
+        };
         
-        emit!(PhaseChanged {
+        emit!(GameModeSet {
             game_id: game.game_id.clone(),
-            new_phase: game.current_phase,
-            day_count: game.day_count,
+            mode,
+        });
+        emit!(GameConfigured {
+            game_id: game.game_id.clone(),
+            settings: game.settings.clone(),
         });
         
         Ok(())
     }
 
-    pub fn night_action(
-        ctx: Context<NightAction>, 
-        action_type: NightActionType,
-        target: Option<Pubkey>
-    ) -> Result<()> {
+    pub fn configure_game(ctx: Context<ConfigureGame>, settings: GameSettings) -> Result<()> {
         let game = &mut ctx.accounts.game;
-        let actor = ctx.accounts.actor.key();
-        
-        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
-        require!(game.current_phase == GamePhase::Night, ErrorCode::NotNightPhase);
-        
-        let actor_index = game.players.iter().position(|p| p.address == actor)
-            .ok_or(ErrorCode::PlayerNotInGame)?;
         
-        require!(game.players[actor_index].is_alive, ErrorCode::PlayerDead);
+        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
+        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotStartable);
+        require!(settings.vigilante_shots > 0, ErrorCode::InvalidSettings);
+        require!(settings.mayor_vote_weight > 0, ErrorCode::InvalidSettings);
+        require!(settings.payout_rule.is_valid(), ErrorCode::InvalidSettings);
         
-        match action_type {
-            NightActionType::MafiaKill => {
-                require!(game.players[actor_index].role == Role::Mafia, ErrorCode::InvalidRole);
-                if let Some(target_addr) = target {
-                    let target_index = game.players.iter().position(|p| p.address == target_addr)
-                        .ok_or(ErrorCode::InvalidTarget)?;
-                    require!(game.players[target_index].is_alive, ErrorCode::InvalidTarget);
-                    
-                    // Mark for elimination (simplified)
-                    game.players[target_index].is_alive = false;
-                    game.eliminated_players.push(target_addr);
-                }
-            },
-            NightActionType::DetectiveInvestigate => {
-                require!(game.players[actor_index].role == Role::Detective, ErrorCode::InvalidRole);
-                // Investigation logic would be handled off-chain for privacy
-            },
-            NightActionType::DoctorHeal => {
-                require!(game.players[actor_index].role == Role::Doctor, ErrorCode::InvalidRole);
-                // Healing logic would prevent mafia kill
-            },
+        game.settings = settings;
+        if !game.settings.ranked {
+            // Only ranked games feed a season
+            game.season = None;
         }
         
-        emit!(NightActionPerformed {
+        emit!(GameConfigured {
             game_id: game.game_id.clone(),
-            actor,
-            action_type,
-            target,
+            settings: game.settings.clone(),
         });
         
         Ok(())
     }
 
-    pub fn claim_winnings(ctx: Context<ClaimWinnings>) -> Result<()> {
-        let claimer = ctx.accounts.claimer.key();
+    pub fn create_season(
+        ctx: Context<CreateSeason>,
+        season_id: u32,
+        start_time: i64,
+        end_time: i64,
+        prize_share_bps: u16,
+    ) -> Result<()> {
+        let season = &mut ctx.accounts.season;
         
-        // Store values before mutable borrow
-        let game_key = ctx.accounts.game.key();
+        require!(start_time < end_time, ErrorCode::InvalidSeason);
+        require!(prize_share_bps <= 10_000, ErrorCode::InvalidSeason);
         
+        season.season_id = season_id;
+        season.authority = ctx.accounts.authority.key();
+        season.start_time = start_time;
+        season.end_time = end_time;
+        season.prize_share_bps = prize_share_bps;
+        season.prize_pool = 0;
+        season.frozen_pool = 0;
+        season.frozen = false;
+        season.leaderboard = Vec::new();
+        
+        emit!(SeasonCreated {
+            season: season.key(),
+            season_id,
+            start_time,
+            end_time,
+            prize_share_bps,
+        });
+        
+        Ok(())
+    }
+
+    pub fn enter_season(ctx: Context<EnterSeason>) -> Result<()> {
         let game = &mut ctx.accounts.game;
-        let game_id = game.game_id.clone();
+        let season = &ctx.accounts.season;
+        let clock = Clock::get()?;
         
-        require!(game.state == GameState::Finished, ErrorCode::GameNotFinished);
-        require!(game.winner.is_some(), ErrorCode::NoWinner);
+        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
+        // The season's authority decides which games count toward its leaderboard
+        require!(season.authority == ctx.accounts.season_authority.key(), ErrorCode::NotSeasonAuthority);
+        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotStartable);
+        require!(game.settings.ranked, ErrorCode::NotRanked);
+        require!(game.mint.is_none(), ErrorCode::InvalidSeason);
+        // The season and protocol cuts both come out of the pot, so together they can't exceed it
+        require!(
+            season.prize_share_bps as u32 + game.rake_bps as u32 <= 10_000,
+            ErrorCode::InvalidSeason
+        );
+        require!(
+            !season.frozen && clock.unix_timestamp >= season.start_time && clock.unix_timestamp < season.end_time,
+            ErrorCode::SeasonNotActive
+        );
         
-        // Check if claimer is a winner
-        let winner = game.winner.as_ref().unwrap();
-        let is_winner = match winner {
-            Winner::Mafia => game.players.iter().any(|p| p.address == claimer && p.role == Role::Mafia && p.is_alive),
-            Winner::Town => game.players.iter().any(|p| p.address == claimer && p.role != Role::Mafia && p.is_alive),
-        };
+        game.season = Some(season.key());
+        game.season_share_bps = season.prize_share_bps;
         
-        require!(is_winner, ErrorCode::NotWinner);
+        emit!(GameEnteredSeason {
+            game_id: game.game_id.clone(),
+            season: season.key(),
+        });
         
-        // Calculate winnings (simplified - equal split among winners)
-        let winner_count = game.players.iter().filter(|p| {
-            p.is_alive && match winner {
-                Winner::Mafia => p.role == Role::Mafia,
-                Winner::Town => p.role != Role::Mafia,
-            }
-        }).count();
+        Ok(())
+    }
+
+    pub fn freeze_season(ctx: Context<FreezeSeason>) -> Result<()> {
+        let season = &mut ctx.accounts.season;
+        let clock = Clock::get()?;
         
-        let winnings = game.entry_fee * game.players.len() as u64 / winner_count as u64;
+        require!(!season.frozen, ErrorCode::SeasonFrozen);
+        require!(clock.unix_timestamp >= season.end_time, ErrorCode::SeasonNotOver);
         
-        // Transfer winnings
-        let ix = anchor_lang::solana_program::system_instruction::transfer(
-            &game_key,
-            &claimer,
-            winnings,
-        );
-        anchor_lang::solana_program::program::invoke(
-            &ix,
-            &[
-                ctx.accounts.game.to_account_info(),
-                ctx.accounts.claimer.to_account_info(),
-            ],
-        )?;
+        // Standings and the pool are locked; anything settled later stays in the account
+        season.frozen = true;
+        season.frozen_pool = season.prize_pool;
         
-        emit!(WinningsClaimed {
-            game_id,
-            claimer,
-            amount: winnings,
+        emit!(SeasonFrozen {
+            season: season.key(),
+            prize_pool: season.frozen_pool,
+            standings: season.leaderboard.clone(),
         });
         
         Ok(())
     }
 
-    pub fn update_game_details(
-        ctx: Context<UpdateGameDetails>,
-        players: Vec<Player>,
-        mafia_members: Vec<Pubkey>,
-        votes: Vec<Vote>,
-        winning_team: Option<Winner>,
-        phase_start_time: i64,
-        phase_end_time: i64,
+    pub fn claim_season_prize(ctx: Context<ClaimSeasonPrize>) -> Result<()> {
+        let season = &mut ctx.accounts.season;
+        let claimer = ctx.accounts.claimer.key();
+        
+        require!(season.frozen, ErrorCode::SeasonNotOver);
+        let rank = season.leaderboard.iter().position(|e| e.player == claimer)
+            .ok_or(ErrorCode::NotWinner)?;
+        require!(!season.leaderboard[rank].claimed, ErrorCode::PrizeAlreadyClaimed);
+        
+        let amount = season.prize_for_rank(rank);
+        season.leaderboard[rank].claimed = true;
+        
+        let season_info = season.to_account_info();
+        **season_info.try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.claimer.to_account_info().try_borrow_mut_lamports()? += amount;
+        
+        emit!(SeasonPrizeClaimed {
+            season: season.key(),
+            claimer,
+            rank: rank as u8,
+            amount,
+        });
+        
+        Ok(())
+    }
+
+    pub fn create_tournament(
+        ctx: Context<CreateTournament>,
+        tournament_id: u32,
+        buy_in: u64,
+        players_per_game: u8,
+        registration_end: i64,
+        prize_split_bps: Vec<u16>,
     ) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        
+        require!(
+            (Tournament::MIN_PLAYERS_PER_GAME..=Tournament::MAX_PLAYERS_PER_GAME).contains(&players_per_game),
+            ErrorCode::InvalidTournament
+        );
+        require!(
+            !prize_split_bps.is_empty() && prize_split_bps.len() <= Tournament::MAX_PLACES,
+            ErrorCode::InvalidTournament
+        );
+        require!(prize_split_bps.iter().map(|bps| *bps as u32).sum::<u32>() == 10_000, ErrorCode::InvalidTournament);
+        
+        tournament.tournament_id = tournament_id;
+        tournament.authority = ctx.accounts.authority.key();
+        tournament.buy_in = buy_in;
+        tournament.players_per_game = players_per_game;
+        tournament.registration_end = registration_end;
+        tournament.state = TournamentState::Registration;
+        tournament.round = 1;
+        tournament.pool = 0;
+        tournament.prize_split_bps = prize_split_bps;
+        tournament.entrants = Vec::new();
+        tournament.games = Vec::new();
+        tournament.round_deadline = 0;
+        
+        emit!(TournamentCreated {
+            tournament: tournament.key(),
+            tournament_id,
+            buy_in,
+            players_per_game,
+            registration_end,
+        });
+        
+        Ok(())
+    }
+
+    pub fn register_tournament(ctx: Context<RegisterTournament>) -> Result<()> {
+        let player = ctx.accounts.player.key();
+        let tournament = &ctx.accounts.tournament;
+        
+        require!(
+            tournament.state == TournamentState::Registration
+                && Clock::get()?.unix_timestamp < tournament.registration_end,
+            ErrorCode::RegistrationClosed
+        );
+        require!(tournament.entrants.len() < Tournament::MAX_ENTRANTS, ErrorCode::TournamentFull);
+        require!(!tournament.entrants.iter().any(|e| e.player == player), ErrorCode::AlreadyJoined);
+        
+        if tournament.buy_in > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.player.to_account_info(),
+                        to: ctx.accounts.tournament.to_account_info(),
+                    },
+                ),
+                tournament.buy_in,
+            )?;
+        }
+        
+        let tournament = &mut ctx.accounts.tournament;
+        tournament.pool += tournament.buy_in;
+        tournament.entrants.push(TournamentEntrant {
+            player,
+            eliminated_round: None,
+            paid_out: false,
+        });
+        
+        emit!(TournamentRegistered {
+            tournament: tournament.key(),
+            player,
+            entrant_count: tournament.entrants.len() as u8,
+        });
+        
+        Ok(())
+    }
+
+    pub fn create_round_game(ctx: Context<CreateRoundGame>) -> Result<()> {
+        let tournament_key = ctx.accounts.tournament.key();
+        let tournament = &mut ctx.accounts.tournament;
         let game = &mut ctx.accounts.game;
+        let clock = Clock::get()?;
         
-        require!(players.len() <= 20, ErrorCode::TooManyPlayers);
-        require!(game.creator == ctx.accounts.authority.key(), ErrorCode::NotCreator);
+        // The first game created after registration closes kicks off the bracket
+        if tournament.state == TournamentState::Registration {
+            require!(clock.unix_timestamp >= tournament.registration_end, ErrorCode::RegistrationOpen);
+            require!(tournament.entrants.len() >= GameSettings::MIN_PLAYERS, ErrorCode::NotEnoughPlayers);
+            tournament.state = TournamentState::InProgress;
+        }
+        require!(tournament.state == TournamentState::InProgress, ErrorCode::TournamentNotInProgress);
         
-        // Clone values for the event
-        let game_id = game.game_id.clone();
-        let player_count = players.len() as u8;
-        let mafia_count = mafia_members.len() as u8;
-        let vote_count = votes.len() as u8;
-        let winner_clone = winning_team.clone();
+        let game_count = tournament.round_game_count();
+        let index = tournament.games.len();
+        require!(index < game_count, ErrorCode::RoundComplete);
+        if index == 0 {
+            tournament.round_deadline = clock.unix_timestamp + Tournament::ROUND_DURATION;
+        }
         
-        // Update game details
-        game.players = players;
-        game.votes = votes;
-        game.phase_start_time = phase_start_time;
-        game.phase_end_time = phase_end_time;
+        // Tournament games have no creator who can sign, so nobody can reconfigure or
+        // rewrite them; fields not set here start zeroed, which reads as empty
+        game.game_id = format!("tournament-{}-round-{}-game-{}", tournament.tournament_id, tournament.round, index + 1);
+        game.creator = tournament_key;
+        game.name = game.game_id.clone();
+        game.max_players = tournament.players_per_game;
+        game.entry_fee = 0;
+        game.is_public = true;
+        game.created_at = clock.unix_timestamp;
+        game.mode = GameMode::Classic;
+        game.settings = GameSettings::default();
         
-        // Update mafia members
-        for player in &mut game.players {
-            if mafia_members.contains(&player.address) {
-                player.role = Role::Mafia;
-            }
+        // Living entrants are dealt round-robin across the round's games
+        game.players = tournament.entrants.iter()
+            .filter(|e| e.eliminated_round.is_none())
+            .enumerate()
+            .filter(|(i, _)| i % game_count == index)
+            .map(|(_, e)| Player::new(e.player, clock.unix_timestamp))
+            .collect();
+        
+        emit!(GameCreated {
+            game_id: game.game_id.clone(),
+            creator: game.creator,
+            max_players: game.max_players,
+            name: game.name.clone(),
+            entry_fee: 0,
+            mint: None,
+            is_public: true,
+        });
+        begin_game(game, &clock)?;
+        
+        tournament.games.push(game.key());
+        
+        emit!(TournamentGameCreated {
+            tournament: tournament_key,
+            round: tournament.round,
+            game: game.key(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn advance_tournament<'info>(ctx: Context<'_, '_, 'info, 'info, AdvanceTournament<'info>>) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        let now = Clock::get()?.unix_timestamp;
+        
+        require!(tournament.state == TournamentState::InProgress, ErrorCode::TournamentNotInProgress);
+        require!(tournament.games.len() == tournament.round_game_count(), ErrorCode::RoundNotFinished);
+        
+        // Every game of the round, in the order they were created
+        require!(ctx.remaining_accounts.len() == tournament.games.len(), ErrorCode::InvalidTournamentGames);
+        let entered = tournament.entrants.iter().filter(|e| e.eliminated_round.is_none()).count();
+        for (key, info) in tournament.games.clone().iter().zip(ctx.remaining_accounts.iter()) {
+            require_keys_eq!(info.key(), *key, ErrorCode::InvalidTournamentGames);
+            let game: Account<'info, Game> = Account::try_from(info)?;
+            tournament.apply_round_game(&game, now)?;
         }
         
-        // Update winner if provided
-        if let Some(winner) = winning_team {
-            game.winner = Some(winner);
-            game.state = GameState::Finished;
+        let survivors = tournament.entrants.iter().filter(|e| e.eliminated_round.is_none()).count();
+        
+        // The final is the round with a single game, or the last round that can still field one;
+        // a round where every game stalled knocks nobody out, so the survivors share the title
+        if tournament.games.len() == 1 || survivors < GameSettings::MIN_PLAYERS || survivors == entered {
+            tournament.state = TournamentState::Finished;
+            
+            emit!(TournamentFinished {
+                tournament: tournament.key(),
+                champions: tournament.entrants.iter()
+                    .filter(|e| e.eliminated_round.is_none())
+                    .map(|e| e.player)
+                    .collect(),
+            });
+        } else {
+            tournament.round += 1;
+            tournament.games.clear();
+            
+            emit!(TournamentRoundAdvanced {
+                tournament: tournament.key(),
+                round: tournament.round,
+                survivors: survivors as u8,
+            });
         }
         
-        emit!(GameUpdated {
-            game_id,
-            player_count,
-            mafia_count,
-            vote_count,
-            winner: winner_clone,
+        Ok(())
+    }
+
+    /// Anyone can call off a tournament whose registration closed without enough
+    /// entrants for a bracket, freeing the buy-ins for refund
+    pub fn cancel_tournament(ctx: Context<CancelTournament>) -> Result<()> {
+        let tournament = &mut ctx.accounts.tournament;
+        
+        require!(tournament.state == TournamentState::Registration, ErrorCode::TournamentNotInRegistration);
+        require!(Clock::get()?.unix_timestamp >= tournament.registration_end, ErrorCode::RegistrationOpen);
+        require!(tournament.entrants.len() < GameSettings::MIN_PLAYERS, ErrorCode::TournamentCanStart);
+        
+        tournament.state = TournamentState::Cancelled;
+        
+        emit!(TournamentCancelled {
+            tournament: tournament.key(),
+            entrant_count: tournament.entrants.len() as u8,
+        });
+        
+        Ok(())
+    }
+
+    pub fn refund_tournament_entry(ctx: Context<RefundTournamentEntry>) -> Result<()> {
+        let entrant = ctx.accounts.entrant.key();
+        let tournament = &mut ctx.accounts.tournament;
+        
+        require!(tournament.state == TournamentState::Cancelled, ErrorCode::TournamentNotCancelled);
+        let index = tournament.entrants.iter().position(|e| e.player == entrant)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        require!(!tournament.entrants[index].paid_out, ErrorCode::AlreadyPaid);
+        
+        let amount = tournament.buy_in;
+        tournament.entrants[index].paid_out = true;
+        tournament.pool -= amount;
+        
+        **tournament.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.entrant.to_account_info().try_borrow_mut_lamports()? += amount;
+        
+        emit!(TournamentEntryRefunded {
+            tournament: tournament.key(),
+            player: entrant,
+            amount,
+        });
+        
+        Ok(())
+    }
+
+    pub fn claim_tournament_prize(ctx: Context<ClaimTournamentPrize>) -> Result<()> {
+        let claimer = ctx.accounts.claimer.key();
+        let tournament = &mut ctx.accounts.tournament;
+        
+        require!(tournament.state == TournamentState::Finished, ErrorCode::TournamentNotFinished);
+        let index = tournament.entrants.iter().position(|e| e.player == claimer)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        require!(!tournament.entrants[index].paid_out, ErrorCode::PrizeAlreadyClaimed);
+        
+        let amount = tournament.prize_for(index);
+        require!(amount > 0, ErrorCode::NotWinner);
+        tournament.entrants[index].paid_out = true;
+        
+        **tournament.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **ctx.accounts.claimer.to_account_info().try_borrow_mut_lamports()? += amount;
+        
+        emit!(TournamentPrizeClaimed {
+            tournament: tournament.key(),
+            player: claimer,
+            amount,
+        });
+        
+        Ok(())
+    }
+
+    pub fn create_prediction_pool(ctx: Context<CreatePredictionPool>, close_day: u16) -> Result<()> {
+        let game = &ctx.accounts.game;
+        let pool = &mut ctx.accounts.pool;
+        
+        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
+        require!(game.state != GameState::Finished, ErrorCode::BettingClosed);
+        require!(!game.creator_override, ErrorCode::CreatorOverride);
+        
+        pool.game = game.key();
+        pool.close_day = close_day;
+        pool.town_total = 0;
+        pool.mafia_total = 0;
+        
+        emit!(PredictionPoolCreated {
+            game_id: game.game_id.clone(),
+            pool: pool.key(),
+            close_day,
+        });
+        ctx.accounts.game.has_prediction_pool = true;
+        
+        Ok(())
+    }
+
+    pub fn place_bet(ctx: Context<PlaceBet>, side: PredictionSide, amount: u64) -> Result<()> {
+        let bettor = ctx.accounts.bettor.key();
+        let game = &ctx.accounts.game;
+        
+        require!(ctx.accounts.pool.is_open(game), ErrorCode::BettingClosed);
+        require!(!game.players.iter().any(|p| p.address == bettor), ErrorCode::BettorCannotPlay);
+        require!(bettor != game.creator, ErrorCode::CreatorCannotBet);
+        require!(amount > 0, ErrorCode::InvalidBet);
+        
+        // A bettor can add to their stake but not switch sides
+        let bet = &mut ctx.accounts.bet;
+        if bet.amount == 0 {
+            bet.bettor = bettor;
+            bet.side = side;
+        }
+        require!(bet.side == side, ErrorCode::InvalidBet);
+        
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.bettor.to_account_info(),
+                    to: ctx.accounts.pool.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        
+        let bet = &mut ctx.accounts.bet;
+        let pool = &mut ctx.accounts.pool;
+        bet.amount += amount;
+        match side {
+            PredictionSide::Town => pool.town_total += amount,
+            PredictionSide::Mafia => pool.mafia_total += amount,
+        }
+        
+        emit!(BetPlaced {
+            game_id: game.game_id.clone(),
+            bettor,
+            side,
+            amount,
+            town_total: pool.town_total,
+            mafia_total: pool.mafia_total,
+        });
+        
+        Ok(())
+    }
+
+    pub fn claim_prediction(ctx: Context<ClaimPrediction>) -> Result<()> {
+        let game = &ctx.accounts.game;
+        let pool = &ctx.accounts.pool;
+        let bet = &mut ctx.accounts.bet;
+        
+        require!(
+            game.state == GameState::Finished || game.state == GameState::Cancelled,
+            ErrorCode::GameNotFinished
+        );
+        require!(!bet.claimed, ErrorCode::PrizeAlreadyClaimed);
+        
+        let payout = pool.payout(game.winner.as_ref(), bet);
+        require!(payout > 0, ErrorCode::NotWinner);
+        bet.claimed = true;
+        
+        **ctx.accounts.pool.to_account_info().try_borrow_mut_lamports()? -= payout;
+        **ctx.accounts.bettor.to_account_info().try_borrow_mut_lamports()? += payout;
+        
+        emit!(PredictionClaimed {
+            game_id: game.game_id.clone(),
+            bettor: bet.bettor,
+            amount: payout,
+        });
+        
+        Ok(())
+    }
+
+    pub fn start_game(ctx: Context<StartGame>) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let clock = Clock::get()?;
+        
+        require!(game.creator == ctx.accounts.creator.key(), ErrorCode::NotCreator);
+        require!(game.state == GameState::WaitingForPlayers, ErrorCode::GameNotStartable);
+        require!(game.players.len() >= GameSettings::MIN_PLAYERS, ErrorCode::NotEnoughPlayers);
+        
+        begin_game(game, &clock)
+    }
+
+    /// Links the Lovers once the slot committed to at the start has passed. Anyone can
+    /// call it; the first day can't end until it has run.
+    pub fn pair_lovers(ctx: Context<PairLovers>) -> Result<()> {
+        let game_key = ctx.accounts.game.key();
+        let game = &mut ctx.accounts.game;
+        let clock = Clock::get()?;
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        let seed_slot = game.lovers_seed_slot.ok_or(ErrorCode::LoversAlreadyPaired)?;
+        require!(clock.slot > seed_slot, ErrorCode::LoversSeedPending);
+        
+        // The sysvar only keeps recent slots; if the committed one has aged out, commit to a new one
+        let data = ctx.accounts.slot_hashes.try_borrow_data()?;
+        let Some(slot_hash) = slot_hash_at(&data, seed_slot)? else {
+            game.lovers_seed_slot = Some(clock.slot + Game::LOVERS_SEED_DELAY_SLOTS);
+            return Ok(());
+        };
+        
+        let seed = hashv(&[&slot_hash, game_key.as_ref()]).to_bytes();
+        let (first, second) = lovers_from_seed(u64::from_le_bytes(seed[..8].try_into().unwrap()), game.players.len());
+        game.players[first].modifier = Modifier::Lover { partner: game.players[second].address };
+        game.players[second].modifier = Modifier::Lover { partner: game.players[first].address };
+        game.lovers_seed_slot = None;
+        
+        emit!(LoversPaired {
+            game_id: game.game_id.clone(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn cast_vote(ctx: Context<CastVote>, target_player: Pubkey) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let voter = ctx.accounts.voter.key();
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        require!(
+            matches!(game.current_phase, GamePhase::Day | GamePhase::Nomination),
+            ErrorCode::NotVotingPhase
+        );
+        
+        let voter_index = game.players.iter().position(|p| p.address == voter)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        let target_index = game.players.iter().position(|p| p.address == target_player)
+            .ok_or(ErrorCode::InvalidTarget)?;
+        
+        require!(game.players[voter_index].is_alive, ErrorCode::PlayerDead);
+        require!(game.players[target_index].is_alive, ErrorCode::InvalidTarget);
+        
+        // Remove previous vote if exists
+        game.votes.retain(|v| v.voter != voter);
+        
+        // Add new vote
+        game.votes.push(Vote {
+            voter,
+            target: target_player,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        game.players[voter_index].vote_target = Some(target_player);
+        
+        emit!(VoteCast {
+            game_id: game.game_id.clone(),
+            voter,
+            target: target_player,
+            total_votes: game.votes.len() as u8,
+        });
+        
+        // Speed games move on as soon as everyone has voted
+        if game.mode == GameMode::Speed && game.current_phase == GamePhase::Day && day_votes_complete(game) {
+            advance_game_phase(game, Clock::get()?.unix_timestamp)?;
+        }
+        
+        Ok(())
+    }
+
+    pub fn reveal_role(ctx: Context<RevealRole>) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        require!(game.current_phase.is_day(), ErrorCode::NotVotingPhase);
+        
+        let player_index = game.players.iter().position(|p| p.address == player)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        
+        require!(game.players[player_index].is_alive, ErrorCode::PlayerDead);
+        require!(game.players[player_index].role == Role::Mayor, ErrorCode::InvalidRole);
+        require!(!game.players[player_index].role_state.revealed, ErrorCode::AlreadyRevealed);
+        
+        // The reveal is public and cannot be undone
+        game.players[player_index].role_state.revealed = true;
+        
+        emit!(RoleRevealed {
+            game_id: game.game_id.clone(),
+            player,
+            role: game.players[player_index].role.clone(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn submit_will(ctx: Context<SubmitWill>, will: Will) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let player = ctx.accounts.player.key();
+        
+        require!(game.state != GameState::Finished, ErrorCode::GameNotActive);
+        
+        let player_index = game.players.iter().position(|p| p.address == player)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        
+        require!(game.players[player_index].is_alive, ErrorCode::PlayerDead);
+        if let Will::Text(text) = &will {
+            require!(text.len() <= Will::MAX_TEXT_LEN, ErrorCode::WillTooLong);
+        }
+        
+        // Game state is public, so a Hash will is how a player keeps the contents private until death
+        game.players[player_index].will = will;
+        
+        emit!(WillSubmitted {
+            game_id: game.game_id.clone(),
+            player,
         });
         
         Ok(())
     }
+
+    pub fn cast_verdict(ctx: Context<CastVerdict>, guilty: bool) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let voter = ctx.accounts.voter.key();
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        require!(game.current_phase == GamePhase::Judgment, ErrorCode::NotVotingPhase);
+        
+        let voter_index = game.players.iter().position(|p| p.address == voter)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        
+        require!(game.players[voter_index].is_alive, ErrorCode::PlayerDead);
+        require!(game.on_trial != Some(voter), ErrorCode::InvalidTarget);
+        
+        game.players[voter_index].verdict = Some(guilty);
+        
+        emit!(VerdictCast {
+            game_id: game.game_id.clone(),
+            voter,
+            guilty,
+        });
+        
+        Ok(())
+    }
+
+    pub fn advance_phase(ctx: Context<AdvancePhase>) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let clock = Clock::get()?;
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        
+        advance_game_phase(game, clock.unix_timestamp)
+    }
+
+    pub fn night_action(
+        ctx: Context<NightAction>, 
+        action_type: NightActionType,
+        target: Option<Pubkey>
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let actor = ctx.accounts.actor.key();
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        require!(game.current_phase == GamePhase::Night, ErrorCode::NotNightPhase);
+        
+        let actor_index = game.players.iter().position(|p| p.address == actor)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        
+        require!(game.players[actor_index].is_alive, ErrorCode::PlayerDead);
+        
+        let target_addr = target.ok_or(ErrorCode::InvalidTarget)?;
+        let target_index = game.players.iter().position(|p| p.address == target_addr)
+            .ok_or(ErrorCode::InvalidTarget)?;
+        require!(game.players[target_index].is_alive, ErrorCode::InvalidTarget);
+        
+        let actor_role = &game.players[actor_index].role;
+        match action_type {
+            NightActionType::MafiaKill => {
+                require!(actor_role.is_mafia(), ErrorCode::InvalidRole);
+                require!(game.mafia_target.is_none(), ErrorCode::MafiaTargetLocked);
+            },
+            NightActionType::DetectiveInvestigate => {
+                require!(*actor_role == Role::Detective, ErrorCode::InvalidRole);
+            },
+            NightActionType::DoctorHeal => {
+                require!(*actor_role == Role::Doctor, ErrorCode::InvalidRole);
+            },
+            NightActionType::VigilanteShoot => {
+                require!(*actor_role == Role::Vigilante, ErrorCode::InvalidRole);
+                require!(game.players[actor_index].role_state.shots_remaining > 0, ErrorCode::NoShotsRemaining);
+                require!(target_addr != actor, ErrorCode::InvalidTarget);
+            },
+            NightActionType::Roleblock => {
+                require!(*actor_role == Role::Escort, ErrorCode::InvalidRole);
+                require!(target_addr != actor, ErrorCode::InvalidTarget);
+            },
+            NightActionType::Guard => {
+                require!(*actor_role == Role::Bodyguard, ErrorCode::InvalidRole);
+                require!(target_addr != actor, ErrorCode::InvalidTarget);
+            },
+            NightActionType::WitchHeal => {
+                require!(*actor_role == Role::Witch, ErrorCode::InvalidRole);
+                require!(!game.players[actor_index].role_state.heal_potion_used, ErrorCode::PotionUsed);
+                let mafia_target = game.mafia_target.ok_or(ErrorCode::MafiaTargetNotSettled)?;
+                require!(target_addr == mafia_target, ErrorCode::InvalidTarget);
+            },
+            NightActionType::WitchPoison => {
+                require!(*actor_role == Role::Witch, ErrorCode::InvalidRole);
+                require!(!game.players[actor_index].role_state.poison_potion_used, ErrorCode::PotionUsed);
+                require!(target_addr != actor, ErrorCode::InvalidTarget);
+            },
+        }
+        
+        // Actions are only recorded here and take effect when the night is resolved. A new
+        // action replaces the actor's last one, except that the Witch's two potions are
+        // separate actions she can both take in one night.
+        let potion = matches!(action_type, NightActionType::WitchHeal | NightActionType::WitchPoison);
+        game.night_actions.retain(|a| a.actor != actor || (potion && a.action_type != action_type));
+        game.night_actions.push(NightActionRecord {
+            actor,
+            action_type,
+            target: target_addr,
+        });
+        
+        // Once every living Mafia member has picked, the kill is settled so the Witch can react to it
+        if action_type == NightActionType::MafiaKill {
+            let all_picked = game.players.iter()
+                .filter(|p| p.is_alive && p.role.is_mafia())
+                .all(|p| game.night_actions.iter().any(|a| a.actor == p.address && a.action_type == NightActionType::MafiaKill));
+            if all_picked {
+                let actions: Vec<&NightActionRecord> = game.night_actions.iter().collect();
+                game.mafia_target = tally_mafia_kill(&actions).map(|(target, _)| target);
+                emit!(MafiaTargetSettled {
+                    game_id: game.game_id.clone(),
+                    day_count: game.day_count,
+                });
+            }
+        }
+        
+        emit!(NightActionPerformed {
+            game_id: game.game_id.clone(),
+            actor,
+            action_type,
+            target,
+        });
+        
+        // Speed games resolve the night as soon as every action is in
+        if game.mode == GameMode::Speed && night_actions_complete(game) {
+            advance_game_phase(game, Clock::get()?.unix_timestamp)?;
+        }
+        
+        Ok(())
+    }
+
+    pub fn hold_seance(ctx: Context<HoldSeance>, spirit: Pubkey) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let medium = ctx.accounts.medium.key();
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        require!(game.current_phase == GamePhase::Night, ErrorCode::NotNightPhase);
+        
+        let medium_index = game.players.iter().position(|p| p.address == medium)
+            .ok_or(ErrorCode::PlayerNotInGame)?;
+        let spirit_index = game.players.iter().position(|p| p.address == spirit)
+            .ok_or(ErrorCode::InvalidTarget)?;
+        
+        require!(game.players[medium_index].is_alive, ErrorCode::PlayerDead);
+        require!(game.players[medium_index].role == Role::Medium, ErrorCode::InvalidRole);
+        require!(!game.players[spirit_index].is_alive, ErrorCode::InvalidTarget);
+        require!(!game.seances.iter().any(|s| s.medium == medium), ErrorCode::SeanceAlreadyOpen);
+        require!(game.seances.len() < Game::MAX_SEANCES, ErrorCode::SeanceLimitReached);
+        
+        // The channel only lasts for the current night
+        let day_count = game.day_count;
+        game.seances.push(Seance {
+            medium,
+            spirit,
+            day_count,
+            message_hashes: Vec::new(),
+        });
+        
+        emit!(SeanceOpened {
+            game_id: game.game_id.clone(),
+            medium,
+            spirit,
+            day_count: game.day_count,
+        });
+        
+        Ok(())
+    }
+
+    pub fn post_seance_message(ctx: Context<PostSeanceMessage>, message_hash: [u8; 32]) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let sender = ctx.accounts.sender.key();
+        let game_id = game.game_id.clone();
+        let day_count = game.day_count;
+        
+        require!(game.state == GameState::Active, ErrorCode::GameNotActive);
+        require!(game.current_phase == GamePhase::Night, ErrorCode::NotNightPhase);
+        
+        let seance = game.seances.iter_mut().find(|s| s.medium == sender || s.spirit == sender)
+            .ok_or(ErrorCode::NotInSeance)?;
+        require!(seance.message_hashes.len() < Seance::MAX_MESSAGES, ErrorCode::SeanceFull);
+        
+        seance.message_hashes.push(message_hash);
+        
+        emit!(SeanceMessagePosted {
+            game_id,
+            medium: seance.medium,
+            sender,
+            message_hash,
+            day_count,
+        });
+        
+        Ok(())
+    }
+
+    pub fn claim_winnings(ctx: Context<ClaimWinnings>) -> Result<()> {
+        let claimer = ctx.accounts.claimer.key();
+        
+        let game = &ctx.accounts.game;
+        let game_id = game.game_id.clone();
+        
+        require!(game.state == GameState::Finished, ErrorCode::GameNotFinished);
+        require!(game.winner.is_some(), ErrorCode::NoWinner);
+        
+        // Check if claimer is owed anything under the payout rule
+        let winner = game.winner.as_ref().unwrap();
+        let index = player_index(game, &claimer)?;
+        let winnings = payouts(game, winner)[index];
+        
+        require!(winnings > 0, ErrorCode::NotWinner);
+        require!(!game.players[index].stats.paid, ErrorCode::AlreadyPaid);
+        require!(Clock::get()?.unix_timestamp <= game.claim_deadline, ErrorCode::ClaimWindowClosed);
+        
+        // Transfer winnings
+        let recipient = payout_destination(game, &ctx.accounts.token_pot, &ctx.accounts.claimer.to_account_info())?;
+        pay_from_pot(game, &ctx.accounts.token_pot, &recipient, winnings)?;
+        ctx.accounts.game.players[index].stats.paid = true;
+        
+        emit!(WinningsClaimed {
+            game_id,
+            claimer,
+            amount: winnings,
+        });
+        
+        Ok(())
+    }
+
+    pub fn settle_game<'info>(ctx: Context<'_, '_, 'info, 'info, SettleGame<'info>>) -> Result<()> {
+        let game = &ctx.accounts.game;
+        
+        require!(game.state == GameState::Finished, ErrorCode::GameNotFinished);
+        require!(Clock::get()?.unix_timestamp <= game.claim_deadline, ErrorCode::ClaimWindowClosed);
+        let winner = game.winner.clone().ok_or(ErrorCode::NoWinner)?;
+        let amounts = payouts(game, &winner);
+        
+        // One recipient per unpaid winner, in `game.players` order. Passing fewer settles
+        // the first page; the rest are picked up by the next call.
+        let owed: Vec<usize> = (0..game.players.len())
+            .filter(|&i| amounts[i] > 0 && !game.players[i].stats.paid)
+            .collect();
+        require!(
+            !ctx.remaining_accounts.is_empty() && ctx.remaining_accounts.len() <= owed.len(),
+            ErrorCode::InvalidPayoutAccounts
+        );
+        
+        let mut paid = Vec::with_capacity(ctx.remaining_accounts.len());
+        for (&index, info) in owed.iter().zip(ctx.remaining_accounts.iter()) {
+            let player = game.players[index].address;
+            if game.mint.is_some() {
+                let token_account = InterfaceAccount::<TokenAccount>::try_from(info)?;
+                require_keys_eq!(token_account.owner, player, ErrorCode::InvalidPayoutAccounts);
+            } else {
+                require_keys_eq!(info.key(), player, ErrorCode::InvalidPayoutAccounts);
+            }
+            
+            pay_from_pot(game, &ctx.accounts.token_pot, info, amounts[index])?;
+            paid.push(index);
+            
+            emit!(WinningsClaimed {
+                game_id: game.game_id.clone(),
+                claimer: player,
+                amount: amounts[index],
+            });
+        }
+        
+        let game = &mut ctx.accounts.game;
+        for &index in &paid {
+            game.players[index].stats.paid = true;
+        }
+        
+        emit!(GameSettled {
+            game_id: game.game_id.clone(),
+            players_paid: paid.len() as u8,
+            players_remaining: (owed.len() - paid.len()) as u8,
+        });
+        
+        Ok(())
+    }
+
+    pub fn sweep_unclaimed(ctx: Context<SweepUnclaimed>) -> Result<()> {
+        let game = &ctx.accounts.game;
+        
+        require!(game.state == GameState::Finished, ErrorCode::GameNotFinished);
+        require!(Clock::get()?.unix_timestamp > game.claim_deadline, ErrorCode::ClaimWindowOpen);
+        let winner = game.winner.clone().ok_or(ErrorCode::NoWinner)?;
+        let amounts = payouts(game, &winner);
+        
+        let unpaid: Vec<usize> = (0..game.players.len())
+            .filter(|&i| amounts[i] > 0 && !game.players[i].stats.paid)
+            .collect();
+        let amount: u64 = unpaid.iter().map(|&i| amounts[i]).sum();
+        
+        // Season games feed their leftovers back into the season's prize pool, as long
+        // as it's still collecting; a frozen season only pays out what it had
+        let season = match game.season {
+            Some(season_key) if ctx.accounts.config.sweep_to_season => {
+                let season = ctx.accounts.season.as_mut().ok_or(ErrorCode::InvalidSeason)?;
+                require_keys_eq!(season.key(), season_key, ErrorCode::InvalidSeason);
+                Some(season).filter(|season| !season.frozen)
+            },
+            _ => None,
+        };
+        let destination = match season {
+            Some(season) => {
+                **game.to_account_info().try_borrow_mut_lamports()? -= amount;
+                **season.to_account_info().try_borrow_mut_lamports()? += amount;
+                season.prize_pool += amount;
+                season.key()
+            },
+            _ => {
+                let recipient = payout_destination(game, &ctx.accounts.token_pot, &ctx.accounts.treasury.to_account_info())?;
+                pay_from_pot(game, &ctx.accounts.token_pot, &recipient, amount)?;
+                ctx.accounts.treasury.key()
+            },
+        };
+        
+        let game = &mut ctx.accounts.game;
+        for &index in &unpaid {
+            game.players[index].stats.paid = true;
+        }
+        
+        emit!(UnclaimedSwept {
+            game_id: game.game_id.clone(),
+            destination,
+            amount,
+        });
+        
+        Ok(())
+    }
+
+    pub fn collect_fees<'info>(ctx: Context<'_, '_, 'info, 'info, CollectFees<'info>>) -> Result<()> {
+        let game = &ctx.accounts.game;
+        
+        require!(game.state == GameState::Finished, ErrorCode::GameNotFinished);
+        require!(!game.fees_collected, ErrorCode::FeesAlreadyCollected);
+        
+        let rake = rake(game);
+        
+        // In SOL games, referrers earn their share of the rake each referred player paid.
+        // Their referral accounts come in order of each referrer's first player.
+        let mut referrals = 0;
+        if game.mint.is_none() {
+            let mut referrers: Vec<Pubkey> = Vec::new();
+            for referrer in game.players.iter().filter_map(|p| p.referrer) {
+                if !referrers.contains(&referrer) {
+                    referrers.push(referrer);
+                }
+            }
+            require!(ctx.remaining_accounts.len() == referrers.len(), ErrorCode::InvalidReferral);
+            
+            let per_player = (rake as u128 * game.referral_share_bps as u128 / 10_000) as u64 / game.players.len() as u64;
+            for (referrer, info) in referrers.iter().zip(ctx.remaining_accounts.iter()) {
+                let mut referral: Account<'info, ReferralAccount> = Account::try_from(info)?;
+                require_keys_eq!(referral.referrer, *referrer, ErrorCode::InvalidReferral);
+                
+                let referred = game.players.iter().filter(|p| p.referrer == Some(*referrer)).count() as u64;
+                let reward = per_player * referred;
+                **game.to_account_info().try_borrow_mut_lamports()? -= reward;
+                **info.try_borrow_mut_lamports()? += reward;
+                referral.pending += reward;
+                referral.total_earned += reward;
+                referral.exit(ctx.program_id)?;
+                referrals += reward;
+                
+                emit!(ReferralCredited {
+                    game_id: game.game_id.clone(),
+                    referrer: *referrer,
+                    amount: reward,
+                });
+            }
+        }
+        
+        // Rounding dust left over by the payout split goes to the treasury with the rake
+        let dust = game.winner.as_ref().map_or(0, |winner| payout_dust(game, winner));
+        let amount = rake - referrals + dust;
+        let recipient = payout_destination(game, &ctx.accounts.token_pot, &ctx.accounts.treasury.to_account_info())?;
+        pay_from_pot(game, &ctx.accounts.token_pot, &recipient, amount)?;
+        
+        let game = &mut ctx.accounts.game;
+        game.fees_collected = true;
+        
+        emit!(FeesCollected {
+            game_id: game.game_id.clone(),
+            treasury: ctx.accounts.treasury.key(),
+            amount,
+            referrals,
+        });
+        
+        Ok(())
+    }
+
+    pub fn record_game_result(ctx: Context<RecordGameResult>, player: Pubkey) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        let profile = &mut ctx.accounts.profile;
+        
+        require!(game.state == GameState::Finished, ErrorCode::GameNotFinished);
+        // Roles, deaths and the winner of an overridden game came from the creator, not from play
+        require!(!game.creator_override, ErrorCode::CreatorOverride);
+        let winner = game.winner.clone().ok_or(ErrorCode::NoWinner)?;
+        
+        let index = player_index(game, &player)?;
+        let winnings = payouts(game, &winner)[index];
+        require!(!game.players[index].stats.result_recorded, ErrorCode::ResultAlreadyRecorded);
+        game.players[index].stats.result_recorded = true;
+        
+        let entry = &game.players[index];
+        profile.games_finished += 1;
+        if on_winning_side(entry, &winner) {
+            if entry.role.is_mafia() {
+                profile.wins_as_mafia += 1;
+            } else {
+                profile.wins_as_town += 1;
+            }
+        }
+        if entry.stats.lynched {
+            profile.times_lynched += 1;
+        }
+        profile.correct_mafia_votes += entry.stats.correct_votes as u32;
+        profile.mafia_lynches += entry.stats.mafia_lynches as u32;
+        profile.successful_heals += entry.stats.successful_heals as u32;
+        if entry.role == Role::Doctor && entry.is_alive {
+            profile.doctor_survivals += 1;
+        }
+        // Token winnings are in the mint's base units, not lamports
+        if game.mint.is_none() {
+            profile.sol_won += winnings;
+        }
+        
+        emit!(GameResultRecorded {
+            game_id: game.game_id.clone(),
+            player,
+            games_finished: profile.games_finished,
+        });
+        
+        Ok(())
+    }
+
+    /// Grows a profile created under an older, shorter layout to the current size;
+    /// the appended fields start zeroed. Anyone can pay for it.
+    pub fn migrate_profile(ctx: Context<MigrateProfile>) -> Result<()> {
+        let profile = ctx.accounts.profile.to_account_info();
+        
+        require!(profile.data_len() < PlayerProfile::SPACE, ErrorCode::ProfileUpToDate);
+        require!(
+            profile.try_borrow_data()?.starts_with(&PlayerProfile::DISCRIMINATOR),
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        
+        let rent = Rent::get()?.minimum_balance(PlayerProfile::SPACE);
+        let top_up = rent.saturating_sub(profile.lamports());
+        if top_up > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: profile.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        profile.realloc(PlayerProfile::SPACE, true)?;
+        
+        emit!(ProfileMigrated {
+            wallet: ctx.accounts.wallet.key(),
+        });
+        
+        Ok(())
+    }
+
+    pub fn settle_ratings<'info>(ctx: Context<'_, '_, 'info, 'info, SettleRatings<'info>>) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        
+        require!(game.state == GameState::Finished, ErrorCode::GameNotFinished);
+        require!(game.settings.ranked, ErrorCode::NotRanked);
+        require!(!game.ratings_settled, ErrorCode::RatingsAlreadySettled);
+        let winner = game.winner.clone().ok_or(ErrorCode::NoWinner)?;
+        
+        // One profile per player, passed in the same order as `game.players`
+        require!(ctx.remaining_accounts.len() == game.players.len(), ErrorCode::InvalidProfileAccounts);
+        let mut profiles: Vec<Account<'info, PlayerProfile>> = Vec::with_capacity(game.players.len());
+        for (player, info) in game.players.iter().zip(ctx.remaining_accounts.iter()) {
+            let (expected, _) = Pubkey::find_program_address(&[b"profile", player.address.as_ref()], ctx.program_id);
+            require_keys_eq!(info.key(), expected, ErrorCode::InvalidProfileAccounts);
+            profiles.push(Account::try_from(info)?);
+        }
+        
+        let average = |mafia: bool| -> u32 {
+            let ratings: Vec<u32> = game.players.iter().zip(profiles.iter())
+                .filter(|(p, _)| p.role.is_mafia() == mafia)
+                .map(|(_, profile)| profile.rating)
+                .collect();
+            (ratings.iter().map(|r| *r as u64).sum::<u64>() / ratings.len().max(1) as u64) as u32
+        };
+        let mafia_average = average(true);
+        let town_average = average(false);
+        
+        let mut changes = Vec::with_capacity(profiles.len());
+        for (player, profile) in game.players.iter().zip(profiles.iter_mut()) {
+            let is_mafia = player.role.is_mafia();
+            let won = on_winning_side(player, &winner);
+            let opponent_average = if is_mafia { town_average } else { mafia_average };
+            
+            let old_rating = profile.rating;
+            profile.rating = updated_rating(old_rating, opponent_average, won);
+            profile.ranked_games += 1;
+            
+            changes.push(RatingChange {
+                player: player.address,
+                old_rating,
+                new_rating: profile.rating,
+            });
+        }
+        
+        for profile in &profiles {
+            profile.exit(ctx.program_id)?;
+        }
+        game.ratings_settled = true;
+        
+        // Season games feed the leaderboard and hand the season its cut of the entry fees.
+        // A frozen season only pays out what it had, so a late cut goes to the treasury.
+        if let Some(season_key) = game.season {
+            let season = ctx.accounts.season.as_mut().ok_or(ErrorCode::InvalidSeason)?;
+            require_keys_eq!(season.key(), season_key, ErrorCode::InvalidSeason);
+            
+            let cut = season_cut(game);
+            **game.to_account_info().try_borrow_mut_lamports()? -= cut;
+            if season.frozen {
+                **ctx.accounts.treasury.try_borrow_mut_lamports()? += cut;
+            } else {
+                for change in &changes {
+                    season.record_rating(change.player, change.new_rating);
+                }
+                **season.to_account_info().try_borrow_mut_lamports()? += cut;
+                season.prize_pool += cut;
+            }
+        }
+        
+        emit!(RatingsSettled {
+            game_id: game.game_id.clone(),
+            changes,
+        });
+        
+        Ok(())
+    }
+
+    pub fn update_game_details(
+        ctx: Context<UpdateGameDetails>,
+        players: Vec<Player>,
+        mafia_members: Vec<Pubkey>,
+        votes: Vec<Vote>,
+        winning_team: Option<Winner>,
+        phase_start_time: i64,
+        phase_end_time: i64,
+    ) -> Result<()> {
+        let game = &mut ctx.accounts.game;
+        
+        require!(players.len() <= 20, ErrorCode::TooManyPlayers);
+        require!(game.creator == ctx.accounts.authority.key(), ErrorCode::NotCreator);
+        require!(!game.settings.ranked, ErrorCode::RankedRequirementsNotMet);
+        // Finished games have payouts and results keyed off the stored players
+        require!(
+            game.state == GameState::WaitingForPlayers || game.state == GameState::Active,
+            ErrorCode::GameNotActive
+        );
+        ensure_overridable(game)?;
+        
+        // Clone values for the event
+        let game_id = game.game_id.clone();
+        let player_count = players.len() as u8;
+        let mafia_count = mafia_members.len() as u8;
+        let vote_count = votes.len() as u8;
+        let winner_clone = winning_team.clone();
+        
+        // Update game details. Stats, role resources, modifiers and referrers are the
+        // program's own bookkeeping, so they are kept from the stored players.
+        let stored = std::mem::take(&mut game.players);
+        game.players = players;
+        for player in &mut game.players {
+            let existing = stored.iter().find(|p| p.address == player.address);
+            player.stats = existing.map(|p| p.stats.clone()).unwrap_or_default();
+            player.role_state = existing.map(|p| p.role_state.clone()).unwrap_or_default();
+            player.modifier = existing.map_or(Modifier::None, |p| p.modifier.clone());
+            player.referrer = existing.and_then(|p| p.referrer);
+        }
+        game.votes = votes;
+        game.phase_start_time = phase_start_time;
+        game.phase_end_time = phase_end_time;
+        game.creator_override = true;
+        
+        // Update mafia members
+        for player in &mut game.players {
+            if mafia_members.contains(&player.address) {
+                player.role = Role::Mafia;
+            }
+        }
+        
+        // Update winner if provided
+        if let Some(winner) = winning_team {
+            game.winner = Some(winner);
+            game.state = GameState::Finished;
+            game.claim_deadline = Clock::get()?.unix_timestamp + game.claim_window_secs as i64;
+        }
+        
+        emit!(GameUpdated {
+            game_id,
+            player_count,
+            mafia_count,
+            vote_count,
+            winner: winner_clone,
+        });
+        
+        Ok(())
+    }
+}
+
+// Helper functions
+/// Deals roles and opens the first day; shared by `start_game` and tournament games
+fn begin_game(game: &mut Game, clock: &Clock) -> Result<()> {
+    if game.roles.is_empty() {
+        // Assign roles randomly (simplified for demo)
+        let mafia_count = std::cmp::max(1, game.players.len() / 4);
+        
+        for (i, player) in game.players.iter_mut().enumerate() {
+            if i < mafia_count {
+                player.role = Role::Mafia;
+            } else if i == mafia_count {
+                player.role = Role::Detective;
+            } else if i == mafia_count + 1 {
+                player.role = Role::Doctor;
+            } else {
+                player.role = Role::Civilian;
+            }
+        }
+    } else {
+        // Deal the configured deck in join order; anyone past the end of it is a Civilian
+        let deck = game.roles.clone();
+        for (i, player) in game.players.iter_mut().enumerate() {
+            player.role = deck.get(i).cloned().unwrap_or(Role::Civilian);
+        }
+        require!(game.players.iter().any(|p| p.role.is_mafia()), ErrorCode::InvalidRoleDeck);
+    }
+    
+    if game.settings.ranked {
+        // Ranked games use the standard deck so ratings compare like with like
+        require!(game.players.len() >= GameSettings::RANKED_MIN_PLAYERS, ErrorCode::RankedRequirementsNotMet);
+        require!(game.roles.is_empty(), ErrorCode::RankedRequirementsNotMet);
+        require!(game.mode == GameMode::Classic, ErrorCode::RankedRequirementsNotMet);
+        require!(!game.settings.lovers_enabled, ErrorCode::RankedRequirementsNotMet);
+    }
+    
+    if game.mode == GameMode::Speed {
+        let settings = &game.settings;
+        require!(!settings.structured_day && !settings.lovers_enabled, ErrorCode::InvalidGameMode);
+        require!(settings.day_secs > 0 && settings.night_secs > 0, ErrorCode::InvalidGameMode);
+        require!(settings.day_secs <= GameSettings::SPEED_MAX_PHASE_SECS, ErrorCode::InvalidGameMode);
+        require!(settings.night_secs <= GameSettings::SPEED_MAX_PHASE_SECS, ErrorCode::InvalidGameMode);
+        require!(game.players.iter().all(|p| p.role.in_speed_deck()), ErrorCode::InvalidGameMode);
+    }
+    
+    // Hand out per-role resources
+    let vigilante_shots = game.settings.vigilante_shots;
+    for player in &mut game.players {
+        player.role_state = RoleState::default();
+        player.modifier = Modifier::None;
+        if player.role == Role::Vigilante {
+            player.role_state.shots_remaining = vigilante_shots;
+        }
+    }
+    
+    // The Lovers are picked from the hash of a slot that hasn't happened yet, so whoever
+    // starts the game can't choose them by timing the start
+    game.lovers_seed_slot = game.settings.lovers_enabled
+        .then_some(clock.slot + Game::LOVERS_SEED_DELAY_SLOTS);
+    
+    game.state = GameState::Active;
+    game.current_phase = if game.settings.structured_day {
+        GamePhase::Discussion
+    } else {
+        GamePhase::Day
+    };
+    game.phase_start_time = clock.unix_timestamp;
+    if let Some(duration) = game.settings.phase_duration(game.current_phase) {
+        game.phase_end_time = clock.unix_timestamp + duration as i64;
+    }
+    game.day_count = 1;
+    
+    emit!(GameStarted {
+        game_id: game.game_id.clone(),
+        player_count: game.players.len() as u8,
+        day_count: game.day_count,
+    });
+    
+    Ok(())
+}
+
+/// Moves an active game into its next phase, settling whatever the current phase decided
+fn advance_game_phase(game: &mut Game, now: i64) -> Result<()> {
+    require!(game.lovers_seed_slot.is_none(), ErrorCode::LoversNotPaired);
+    
+    match game.current_phase {
+        GamePhase::Day => {
+            if game.settings.phase_duration(GamePhase::Day).is_some() {
+                require!(now >= game.phase_end_time || day_votes_complete(game), ErrorCode::PhaseNotOver);
+            }
+            
+            // Process day votes and eliminate player
+            for dead in process_day_votes(game)? {
+                emit_player_eliminated(game, dead, game.current_phase);
+            }
+            
+            begin_night(game);
+        },
+        GamePhase::Night => {
+            if game.settings.phase_duration(GamePhase::Night).is_some() {
+                require!(now >= game.phase_end_time || night_actions_complete(game), ErrorCode::PhaseNotOver);
+            }
+            
+            resolve_night(game)?;
+            game.seances.clear();
+            game.current_phase = if game.settings.structured_day {
+                GamePhase::Discussion
+            } else {
+                GamePhase::Day
+            };
+            game.day_count += 1;
+        },
+        GamePhase::Discussion => {
+            require!(now >= game.phase_end_time, ErrorCode::PhaseNotOver);
+            game.current_phase = GamePhase::Nomination;
+        },
+        GamePhase::Nomination => {
+            // Nominations can cut the phase short by putting someone on trial
+            match nominated_player(game) {
+                Some(accused) => {
+                    game.on_trial = Some(accused);
+                    game.current_phase = GamePhase::Defense;
+                    
+                    emit!(TrialStarted {
+                        game_id: game.game_id.clone(),
+                        accused,
+                        day_count: game.day_count,
+                    });
+                },
+                None => {
+                    require!(now >= game.phase_end_time, ErrorCode::PhaseNotOver);
+                    begin_night(game);
+                },
+            }
+        },
+        GamePhase::Defense => {
+            require!(now >= game.phase_end_time, ErrorCode::PhaseNotOver);
+            game.current_phase = GamePhase::Judgment;
+        },
+        GamePhase::Judgment => {
+            require!(now >= game.phase_end_time, ErrorCode::PhaseNotOver);
+            let accused = game.on_trial.take().ok_or(ErrorCode::InvalidPhase)?;
+            
+            let mut guilty_votes = 0;
+            let mut innocent_votes = 0;
+            for player in game.players.iter().filter(|p| p.is_alive) {
+                match player.verdict {
+                    Some(true) => guilty_votes += vote_weight(game, &player.address),
+                    Some(false) => innocent_votes += vote_weight(game, &player.address),
+                    None => {},
+                }
+            }
+            let guilty = guilty_votes > innocent_votes;
+            
+            emit!(TrialVerdict {
+                game_id: game.game_id.clone(),
+                accused,
+                guilty,
+                guilty_votes,
+                innocent_votes,
+                day_count: game.day_count,
+            });
+            
+            let accused_index = player_index(game, &accused)?;
+            if game.players[accused_index].role.is_mafia() {
+                for player in game.players.iter_mut().filter(|p| p.is_alive && p.verdict == Some(true)) {
+                    player.stats.correct_votes += 1;
+                    if guilty {
+                        player.stats.mafia_lynches += 1;
+                    }
+                }
+            }
+            
+            // Only a guilty verdict eliminates the accused
+            if guilty {
+                game.players[accused_index].stats.lynched = true;
+                for dead in eliminate_player(game, accused_index) {
+                    emit_player_eliminated(game, dead, game.current_phase);
+                }
+            }
+            
+            begin_night(game);
+        },
+        GamePhase::Lobby => return Err(ErrorCode::InvalidPhase.into()),
+    }
+    
+    game.phase_start_time = now;
+    if let Some(duration) = game.settings.phase_duration(game.current_phase) {
+        game.phase_end_time = now + duration as i64;
+    }
+    game.votes.clear();
+    
+    // Clear vote targets
+    for player in &mut game.players {
+        player.vote_target = None;
+        player.verdict = None;
+    }
+    
+    // Check win conditions
+    let win_result = check_win_condition(game)?;
+    if let Some(winner) = win_result {
+        game.state = GameState::Finished;
+        game.winner = Some(winner.clone());
+        game.claim_deadline = now + game.claim_window_secs as i64;
+        
+        emit!(GameEnded {
+            game_id: game.game_id.clone(),
+            winner,
+            day_count: game.day_count,
+            players: game.players.iter().map(|p| PlayerReveal {
+                address: p.address,
+                role: p.role.clone(),
+                is_alive: p.is_alive,
+                modifier: p.modifier.clone(),
+                role_state: p.role_state.clone(),
+            }).collect(),
+        });
+    }
+    
+    emit!(PhaseChanged {
+        game_id: game.game_id.clone(),
+        new_phase: game.current_phase,
+        day_count: game.day_count,
+    });
+    
+    Ok(())
+}
+
+/// Whether every living player has cast a day vote
+fn day_votes_complete(game: &Game) -> bool {
+    game.players.iter().filter(|p| p.is_alive).all(|p| p.vote_target.is_some())
+}
+
+/// Whether every living player with a night action has submitted one
+fn night_actions_complete(game: &Game) -> bool {
+    game.players.iter()
+        .filter(|p| p.is_alive && p.role.has_night_action())
+        .all(|p| game.night_actions.iter().any(|a| a.actor == p.address))
+}
+
+/// Returns the indices of everyone who died as a result of the day's vote
+fn process_day_votes(game: &mut Game) -> Result<Vec<usize>> {
+    let mut vote_counts: HashMap<Pubkey, u32> = HashMap::new();
+    
+    for vote in &game.votes {
+        *vote_counts.entry(vote.target).or_insert(0) += vote_weight(game, &vote.voter);
+    }
+    
+    // Credit everyone who voted for a Mafia member
+    let correct_voters: Vec<usize> = game.votes.iter()
+        .filter(|v| game.players.iter().any(|p| p.address == v.target && p.role.is_mafia()))
+        .filter_map(|v| game.players.iter().position(|p| p.address == v.voter))
+        .collect();
+    for index in correct_voters {
+        game.players[index].stats.correct_votes += 1;
+    }
+    
+    if let Some((most_voted, _)) = vote_counts.iter().max_by_key(|(_, count)| *count) {
+        let target_index = game.players.iter().position(|p| p.address == *most_voted).unwrap();
+        game.players[target_index].stats.lynched = true;
+        
+        // Voters who helped lynch a Mafia member
+        if game.players[target_index].role.is_mafia() {
+            let lynchers: Vec<usize> = game.votes.iter()
+                .filter(|v| v.target == *most_voted)
+                .filter_map(|v| game.players.iter().position(|p| p.address == v.voter))
+                .collect();
+            for index in lynchers {
+                game.players[index].stats.mafia_lynches += 1;
+            }
+        }
+        return Ok(eliminate_player(game, target_index));
+    }
+    
+    Ok(Vec::new())
+}
+
+/// Handles everything that happens as night falls and moves the game into the night
+fn begin_night(game: &mut Game) {
+    // Vigilantes who shot a Town member die of guilt as the next night falls
+    let guilty: Vec<usize> = game.players.iter().enumerate()
+        .filter(|(_, p)| p.is_alive && p.role_state.guilt_night == Some(game.day_count))
+        .map(|(i, _)| i)
+        .collect();
+    for index in guilty {
+        if !game.players[index].is_alive {
+            continue;
+        }
+        for dead in eliminate_player(game, index) {
+            emit_player_eliminated(game, dead, GamePhase::Night);
+        }
+    }
+    
+    game.on_trial = None;
+    game.current_phase = GamePhase::Night;
+}
+
+/// The first player whose nominations reach a majority of the living
+fn nominated_player(game: &Game) -> Option<Pubkey> {
+    let needed = game.players.iter().filter(|p| p.is_alive).count() as u32 / 2 + 1;
+    let mut nominations: Vec<(Pubkey, u32)> = Vec::new();
+    for vote in &game.votes {
+        match nominations.iter_mut().find(|(target, _)| *target == vote.target) {
+            Some((_, count)) => *count += vote_weight(game, &vote.voter),
+            None => nominations.push((vote.target, vote_weight(game, &vote.voter))),
+        }
+    }
+    nominations.into_iter().find(|(_, count)| *count >= needed).map(|(target, _)| target)
+}
+
+/// Elo update against the opposing side's average rating
+fn updated_rating(rating: u32, opponent_average: u32, won: bool) -> u32 {
+    // Expected score in basis points for rating gaps of 0, 50, ..., 800 in the opponent's favour
+    const EXPECTED: [u32; 17] = [
+        5000, 4285, 3599, 2966, 2403, 1917, 1510, 1177, 909, 698, 532, 405, 307, 232, 175, 132, 99,
+    ];
+    
+    let gap = (opponent_average as i64 - rating as i64).clamp(-800, 800);
+    let step = (gap.unsigned_abs() / 50) as usize;
+    let fraction = (gap.unsigned_abs() % 50) as u32;
+    let upper = EXPECTED[step];
+    let lower = EXPECTED[(step + 1).min(EXPECTED.len() - 1)];
+    let mut expected = upper - (upper - lower) * fraction / 50;
+    if gap < 0 {
+        expected = 10_000 - expected;
+    }
+    
+    let actual: i64 = if won { 10_000 } else { 0 };
+    let change = PlayerProfile::RATING_K as i64 * (actual - expected as i64) / 10_000;
+    (rating as i64 + change).max(0) as u32
+}
+
+/// How many votes a player's day vote counts for
+fn vote_weight(game: &Game, voter: &Pubkey) -> u32 {
+    match game.players.iter().find(|p| p.address == *voter) {
+        Some(p) if p.role == Role::Mayor && p.role_state.revealed => game.settings.mayor_vote_weight as u32,
+        _ => 1,
+    }
+}
+
+/// Resolves the actions recorded during the night. The order is fixed:
+/// 1. Roleblocks: every blocked player's action is dropped. Blocks land
+///    simultaneously, so an Escort cannot be blocked out of their own block.
+/// 2. Protection: Doctors and the Witch's heal shield their targets from kills.
+/// 3. Kills: the Mafia's most chosen target, every Vigilante shot and the
+///    Witch's poison land
+///    unless the target is protected. An attack on a guarded player kills the
+///    Bodyguard instead, and the attacker too unless they are night immune.
+/// 4. Investigations: surviving Detectives learn how their target appears.
+fn resolve_night(game: &mut Game) -> Result<()> {
+    let actions = std::mem::take(&mut game.night_actions);
+    let settled_target = game.mafia_target.take();
+    
+    // 1. Roleblocks
+    let blocked: Vec<Pubkey> = actions.iter()
+        .filter(|a| a.action_type == NightActionType::Roleblock)
+        .map(|a| a.target)
+        .collect();
+    let active: Vec<&NightActionRecord> = actions.iter()
+        .filter(|a| a.action_type == NightActionType::Roleblock || !blocked.contains(&a.actor))
+        .collect();
+    
+    // 2. Protection
+    let protected: Vec<Pubkey> = active.iter()
+        .filter(|a| matches!(a.action_type, NightActionType::DoctorHeal | NightActionType::WitchHeal))
+        .map(|a| a.target)
+        .collect();
+    for action in active.iter().filter(|a| a.action_type == NightActionType::WitchHeal) {
+        let witch_index = player_index(game, &action.actor)?;
+        game.players[witch_index].role_state.heal_potion_used = true;
+    }
+    
+    // 3. Kills
+    let mut attacks: Vec<(Pubkey, Pubkey, DeathCause)> = Vec::new();
+    // A settled target is the one the Witch saw, so it stands; any Mafia member who
+    // wasn't blocked carries it out, preferring one who picked it
+    let mafia_kill = match settled_target {
+        Some(target) => active.iter()
+            .filter(|a| a.action_type == NightActionType::MafiaKill)
+            .min_by_key(|a| a.target != target)
+            .map(|a| (target, a.actor)),
+        None => tally_mafia_kill(&active),
+    };
+    if let Some((target, attacker)) = mafia_kill {
+        attacks.push((attacker, target, DeathCause::MafiaKill));
+    }
+    
+    for action in active.iter().filter(|a| a.action_type == NightActionType::VigilanteShoot) {
+        let shooter_index = player_index(game, &action.actor)?;
+        let shooter = &mut game.players[shooter_index];
+        if shooter.role_state.shots_remaining == 0 {
+            continue;
+        }
+        shooter.role_state.shots_remaining -= 1;
+        attacks.push((action.actor, action.target, DeathCause::VigilanteShot));
+    }
+    
+    for action in active.iter().filter(|a| a.action_type == NightActionType::WitchPoison) {
+        let witch_index = player_index(game, &action.actor)?;
+        game.players[witch_index].role_state.poison_potion_used = true;
+        attacks.push((action.actor, action.target, DeathCause::Poison));
+    }
+    
+    // Each Bodyguard intercepts at most one attack on the player they guard
+    let mut guards: Vec<(Pubkey, Pubkey)> = active.iter()
+        .filter(|a| a.action_type == NightActionType::Guard)
+        .map(|a| (a.actor, a.target))
+        .collect();
+    
+    let mut deaths: Vec<NightDeath> = Vec::new();
+    let mut saved: Vec<Pubkey> = Vec::new();
+    for (attacker, target, cause) in attacks {
+        if protected.contains(&target) {
+            saved.push(target);
+            continue;
+        }
+        if let Some(position) = guards.iter().position(|(_, guarded)| *guarded == target) {
+            let (bodyguard, _) = guards.remove(position);
+            deaths.push(NightDeath { player: bodyguard, cause: DeathCause::DiedGuarding, killer: attacker });
+            let attacker_index = player_index(game, &attacker)?;
+            if !game.players[attacker_index].role.is_night_immune() {
+                deaths.push(NightDeath { player: attacker, cause: DeathCause::BodyguardCounter, killer: bodyguard });
+            }
+            continue;
+        }
+        deaths.push(NightDeath { player: target, cause, killer: attacker });
+    }
+    
+    for action in active.iter().filter(|a| matches!(a.action_type, NightActionType::DoctorHeal | NightActionType::WitchHeal)) {
+        if saved.contains(&action.target) {
+            let healer_index = player_index(game, &action.actor)?;
+            game.players[healer_index].stats.successful_heals += 1;
+        }
+    }
+    
+    // A player hit more than once is only reported for the first hit
+    let mut applied: Vec<NightDeath> = Vec::new();
+    for death in deaths {
+        let index = player_index(game, &death.player)?;
+        if !game.players[index].is_alive {
+            continue;
+        }
+        let dead = eliminate_player(game, index);
+        if death.cause == DeathCause::VigilanteShot && !game.players[index].role.is_mafia() {
+            let shooter_index = player_index(game, &death.killer)?;
+            game.players[shooter_index].role_state.guilt_night = Some(game.day_count + 1);
+        }
+        let victim = death.player;
+        applied.push(death);
+        for lover in dead.into_iter().skip(1) {
+            applied.push(NightDeath {
+                player: game.players[lover].address,
+                cause: DeathCause::Heartbreak,
+                killer: victim,
+            });
+        }
+    }
+    for death in &applied {
+        let index = player_index(game, &death.player)?;
+        emit_player_eliminated(game, index, GamePhase::Night);
+    }
+    
+    // 4. Investigations
+    for action in active.iter().filter(|a| a.action_type == NightActionType::DetectiveInvestigate) {
+        let detective_index = player_index(game, &action.actor)?;
+        if !game.players[detective_index].is_alive {
+            continue;
+        }
+        let target_index = player_index(game, &action.target)?;
+        emit!(PlayerInvestigated {
+            game_id: game.game_id.clone(),
+            detective: action.actor,
+            target: action.target,
+            result: game.players[target_index].role.investigation_result(),
+        });
+    }
+    
+    // What living players did would give their roles away, so only the fact that an action
+    // was blocked is announced. Causes and killers say as much about roles as a full reveal.
+    let full_reveal = game.settings.reveal_on_death == RevealOnDeath::FullRole;
+    emit!(NightResolved {
+        game_id: game.game_id.clone(),
+        day_count: game.day_count,
+        blocked: actions.iter()
+            .filter(|a| a.action_type != NightActionType::Roleblock && blocked.contains(&a.actor))
+            .map(|a| a.actor)
+            .collect(),
+        deaths: applied.iter().map(|death| NightDeathReport {
+            player: death.player,
+            cause: full_reveal.then_some(death.cause),
+            killer: full_reveal.then_some(death.killer),
+        }).collect(),
+    });
+    
+    Ok(())
+}
+
+/// The Mafia's night target, picked by most votes, and the first member to pick it,
+/// who carries out the kill
+fn tally_mafia_kill(actions: &[&NightActionRecord]) -> Option<(Pubkey, Pubkey)> {
+    let mut mafia_votes: Vec<(Pubkey, Pubkey, u32)> = Vec::new();
+    for action in actions.iter().filter(|a| a.action_type == NightActionType::MafiaKill) {
+        match mafia_votes.iter_mut().find(|(target, _, _)| *target == action.target) {
+            Some((_, _, count)) => *count += 1,
+            None => mafia_votes.push((action.target, action.actor, 1)),
+        }
+    }
+    
+    let mut best: Option<(Pubkey, Pubkey)> = None;
+    let mut best_count = 0;
+    for (target, attacker, count) in mafia_votes {
+        if count > best_count {
+            best = Some((target, attacker));
+            best_count = count;
+        }
+    }
+    best
+}
+
+fn player_index(game: &Game, address: &Pubkey) -> Result<usize> {
+    game.players.iter().position(|p| p.address == *address)
+        .ok_or(ErrorCode::PlayerNotInGame.into())
+}
+
+/// Hash of the first slot at or after `slot`, read from raw SlotHashes sysvar data,
+/// which lists slots newest first. `None` once that slot has aged out of the sysvar.
+fn slot_hash_at(data: &[u8], slot: u64) -> Result<Option<[u8; 32]>> {
+    const ENTRY: usize = 8 + 32;
+    let count = data.get(..8).map_or(0, |len| u64::from_le_bytes(len.try_into().unwrap())) as usize;
+    let entries = data.get(8..8 + count * ENTRY).ok_or(ErrorCode::InvalidSlotHashes)?;
+    
+    let mut found = None;
+    for entry in entries.chunks_exact(ENTRY) {
+        let entry_slot = u64::from_le_bytes(entry[..8].try_into().unwrap());
+        if entry_slot < slot {
+            break;
+        }
+        found = Some(entry[8..].try_into().unwrap());
+        if entry_slot == slot {
+            return Ok(found);
+        }
+    }
+    
+    match (found, entries.chunks_exact(ENTRY).last()) {
+        // Nothing at or after the slot has landed yet
+        (None, _) => err!(ErrorCode::LoversSeedPending),
+        // Every entry is newer, so the slot (or the one after it) may have been dropped
+        (Some(_), Some(oldest)) if u64::from_le_bytes(oldest[..8].try_into().unwrap()) > slot => Ok(None),
+        (Some(hash), _) => Ok(Some(hash)),
+    }
+}
+
+/// Two distinct player indices picked from a random seed
+fn lovers_from_seed(seed: u64, player_count: usize) -> (usize, usize) {
+    let player_count = player_count as u64;
+    let first = seed % player_count;
+    let second = (first + 1 + (seed / player_count) % (player_count - 1)) % player_count;
+    (first as usize, second as usize)
+}
+
+/// Kills the player at `index` along with anyone whose fate is tied to theirs.
+/// Returns the indices of everyone who died, starting with `index`.
+fn eliminate_player(game: &mut Game, index: usize) -> Vec<usize> {
+    let mut dead = vec![index];
+    game.players[index].is_alive = false;
+    let address = game.players[index].address;
+    game.eliminated_players.push(address);
+    
+    // A Lover dies of heartbreak right away
+    if let Modifier::Lover { partner } = game.players[index].modifier {
+        if let Some(partner_index) = game.players.iter().position(|p| p.address == partner && p.is_alive) {
+            game.players[partner_index].is_alive = false;
+            game.eliminated_players.push(partner);
+            dead.push(partner_index);
+        }
+    }
+    
+    for &i in &dead {
+        if game.players[i].will != Will::None {
+            emit!(WillRevealed {
+                game_id: game.game_id.clone(),
+                player: game.players[i].address,
+                will: game.players[i].will.clone(),
+            });
+        }
+    }
+    
+    dead
+}
+
+/// Announces a death, revealing as much about the player as the game's rules allow
+fn emit_player_eliminated(game: &Game, index: usize, phase: GamePhase) {
+    let player = &game.players[index];
+    let revealed = match game.settings.reveal_on_death {
+        RevealOnDeath::FullRole => DeathReveal::Role(player.role.clone()),
+        RevealOnDeath::AlignmentOnly => DeathReveal::Alignment(player.role.alignment()),
+        RevealOnDeath::Hidden => DeathReveal::Hidden,
+    };
+    
+    emit!(PlayerEliminated {
+        game_id: game.game_id.clone(),
+        player: player.address,
+        phase,
+        day_count: game.day_count,
+        revealed,
+    });
+}
+
+/// Whether a player's side won, whether or not they survived
+/// The creator can only rewrite a game that outsiders have no money riding on
+fn ensure_overridable(game: &Game) -> Result<()> {
+    require!(!game.has_prediction_pool && game.sponsor_pool == 0, ErrorCode::OutsideStakes);
+    Ok(())
+}
+
+fn on_winning_side(player: &Player, winner: &Winner) -> bool {
+    match winner {
+        Winner::Mafia => player.role.is_mafia(),
+        Winner::Town => !player.role.is_mafia(),
+        Winner::Lovers => matches!(player.modifier, Modifier::Lover { .. }),
+    }
+}
+
+/// The part of the entry fees that goes to the game's season prize pool
+fn season_cut(game: &Game) -> u64 {
+    match game.season {
+        Some(_) => (game.pot as u128 * game.season_share_bps as u128 / 10_000) as u64,
+        None => 0,
+    }
+}
+
+/// The protocol's cut of the entry fees, held in the game until collected
+fn rake(game: &Game) -> u64 {
+    (game.pot as u128 * game.rake_bps as u128 / 10_000) as u64
+}
+
+/// Checks the token accounts belong to this game and returns them with the escrow signer bump
+fn token_pot_accounts<'a, 'info>(
+    game: &Account<'info, Game>,
+    token_pot: &'a TokenPot<'info>,
+) -> Result<TokenPotAccounts<'a, 'info>> {
+    let (Some(mint), Some(escrow), Some(vault), Some(token_program)) = (
+        &token_pot.mint,
+        &token_pot.escrow,
+        &token_pot.vault,
+        &token_pot.token_program,
+    ) else {
+        return err!(ErrorCode::MissingTokenAccounts);
+    };
+    
+    require!(game.mint == Some(mint.key()), ErrorCode::InvalidMint);
+    let escrow_key = Pubkey::create_program_address(
+        &[b"escrow", game.key().as_ref(), &[game.escrow_bump]],
+        &crate::ID,
+    ).map_err(|_| error!(ErrorCode::InvalidEscrow))?;
+    require_keys_eq!(escrow.key(), escrow_key, ErrorCode::InvalidEscrow);
+    require!(vault.owner == escrow_key && vault.mint == mint.key(), ErrorCode::InvalidEscrow);
+    
+    Ok(TokenPotAccounts { mint, escrow, vault, token_program })
+}
+
+/// Where a payout to `wallet` lands: the wallet itself for SOL games, or its token account in `token_pot`
+fn payout_destination<'info>(
+    game: &Game,
+    token_pot: &TokenPot<'info>,
+    wallet: &AccountInfo<'info>,
+) -> Result<AccountInfo<'info>> {
+    if game.mint.is_none() {
+        return Ok(wallet.clone());
+    }
+    
+    let token_account = token_pot.token_account.as_ref().ok_or(ErrorCode::MissingTokenAccounts)?;
+    require_keys_eq!(token_account.owner, wallet.key(), ErrorCode::InvalidPayoutAccounts);
+    Ok(token_account.to_account_info())
+}
+
+/// Moves the entry fee into the pot and returns what the pot actually received,
+/// which for Token-2022 mints with a transfer fee is less than `amount`
+fn deposit_to_pot<'info>(
+    game: &Account<'info, Game>,
+    token_pot: &TokenPot<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<u64> {
+    if game.mint.is_none() {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: game.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        return Ok(amount);
+    }
+    
+    let accounts = token_pot_accounts(game, token_pot)?;
+    let source = token_pot.token_account.as_ref().ok_or(ErrorCode::MissingTokenAccounts)?;
+    let before = accounts.vault.amount;
+    token_interface::transfer_checked(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: source.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: accounts.vault.to_account_info(),
+                authority: payer.to_account_info(),
+            },
+        ),
+        amount,
+        accounts.mint.decimals,
+    )?;
+    let after = anchor_spl::token::accessor::amount(&accounts.vault.to_account_info())?;
+    
+    Ok(after - before)
+}
+
+/// Pays `amount` out of the pot, in lamports from the game or tokens from its vault.
+/// `recipient` is a wallet for SOL games and a token account for token games.
+fn pay_from_pot<'info>(
+    game: &Account<'info, Game>,
+    token_pot: &TokenPot<'info>,
+    recipient: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    
+    if game.mint.is_none() {
+        **game.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **recipient.try_borrow_mut_lamports()? += amount;
+        return Ok(());
+    }
+    
+    let accounts = token_pot_accounts(game, token_pot)?;
+    let game_key = game.key();
+    let signer_seeds: &[&[&[u8]]] = &[&[b"escrow", game_key.as_ref(), &[game.escrow_bump]]];
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            TransferChecked {
+                from: accounts.vault.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                to: recipient.clone(),
+                authority: accounts.escrow.to_account_info(),
+            },
+            signer_seeds,
+        ),
+        amount,
+        accounts.mint.decimals,
+    )
+}
+
+/// The pot left for players once the season and protocol have taken their cuts,
+/// plus any sponsor bonus, which is paid out in full
+fn prize_pool(game: &Game) -> u64 {
+    game.pot.saturating_sub(season_cut(game) + rake(game)) + game.sponsor_pool
+}
+
+/// What each player is owed from the prize pool under the game's payout rule, in `game.players` order
+fn payouts(game: &Game, winner: &Winner) -> Vec<u64> {
+    let rule = game.settings.payout_rule;
+    let prize = prize_pool(game);
+    let mut amounts = vec![0; game.players.len()];
+    
+    // Without an MVP the bonus simply stays in the shared pool
+    let mvp = match rule {
+        PayoutRule::MvpBonus { bonus_bps } => mvp_index(game, winner).map(|index| (index, (prize as u128 * bonus_bps as u128 / 10_000) as u64)),
+        _ => None,
+    };
+    let pool = prize - mvp.map_or(0, |(_, bonus)| bonus);
+    
+    let shares: Vec<u64> = game.players.iter().map(|p| rule.shares(p, winner)).collect();
+    let total_shares: u64 = shares.iter().sum();
+    if total_shares == 0 {
+        return amounts;
+    }
+    for (amount, share) in amounts.iter_mut().zip(shares.iter()) {
+        *amount = (pool as u128 * *share as u128 / total_shares as u128) as u64;
+    }
+    if let Some((index, bonus)) = mvp {
+        amounts[index] += bonus;
+    }
+    
+    amounts
+}
+
+/// Prize pool left over after integer division of the payouts
+fn payout_dust(game: &Game, winner: &Winner) -> u64 {
+    prize_pool(game) - payouts(game, winner).iter().sum::<u64>()
+}
+
+/// The winning-side player with the most correct votes; ties go to whoever joined first
+fn mvp_index(game: &Game, winner: &Winner) -> Option<usize> {
+    game.players.iter()
+        .enumerate()
+        .filter(|(_, p)| on_winning_side(p, winner) && p.stats.correct_votes > 0)
+        .rev()
+        .max_by_key(|(_, p)| p.stats.correct_votes)
+        .map(|(index, _)| index)
+}
+
+fn check_win_condition(game: &Game) -> Result<Option<Winner>> {
+    // Lovers who outlive everyone else win together, whatever their roles
+    let alive: Vec<&Player> = game.players.iter().filter(|p| p.is_alive).collect();
+    if let [first, second] = alive.as_slice() {
+        if first.modifier == (Modifier::Lover { partner: second.address }) {
+            return Ok(Some(Winner::Lovers));
+        }
+    }
+    
+    let alive_mafia = game.players.iter().filter(|p| p.is_alive && p.role.is_mafia()).count();
+    let alive_town = game.players.iter().filter(|p| p.is_alive && !p.role.is_mafia()).count();
+    
+    if alive_mafia == 0 {
+        return Ok(Some(Winner::Town));
+    }
+    
+    if alive_mafia >= alive_town {
+        return Ok(Some(Winner::Mafia));
+    }
+    
+    Ok(None)
+}
+
+#[derive(Accounts)]
+pub struct InitializeCounter<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = GameCounter::SPACE,
+        seeds = [b"game_counter"],
+        bump
+    )]
+    pub counter: Account<'info, GameCounter>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProtocolConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = ProtocolConfig::SPACE,
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    /// Only whoever can upgrade the program gets to set it up
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ ErrorCode::NotAdmin)]
+    pub program: Program<'info, crate::program::SolanaContract>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ ErrorCode::NotAdmin)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreateReferralAccount<'info> {
+    #[account(
+        init,
+        payer = referrer,
+        space = ReferralAccount::SPACE,
+        seeds = [b"referral", referrer.key().as_ref()],
+        bump
+    )]
+    pub referral: Account<'info, ReferralAccount>,
+    #[account(
+        seeds = [b"profile", referrer.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, PlayerProfile>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferral<'info> {
+    #[account(
+        mut,
+        seeds = [b"referral", referrer.key().as_ref()],
+        bump
+    )]
+    pub referral: Account<'info, ReferralAccount>,
+    #[account(mut)]
+    pub referrer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureBadges<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = BadgeConfig::SPACE,
+        seeds = [b"badge_config"],
+        bump
+    )]
+    pub badges: Account<'info, BadgeConfig>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(achievement: Achievement)]
+pub struct MintAchievement<'info> {
+    /// Exists once the badge is minted, so each wallet gets each achievement once
+    #[account(
+        init,
+        payer = wallet,
+        space = AchievementReceipt::SPACE,
+        seeds = [b"achievement", wallet.key().as_ref(), &[achievement as u8]],
+        bump
+    )]
+    pub receipt: Account<'info, AchievementReceipt>,
+    #[account(
+        seeds = [b"profile", wallet.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, PlayerProfile>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    #[account(
+        seeds = [b"badge_config"],
+        bump
+    )]
+    pub badges: Account<'info, BadgeConfig>,
+    /// CHECK: PDA that signs as tree delegate and collection authority
+    #[account(
+        seeds = [b"badge_authority"],
+        bump
+    )]
+    pub badge_authority: UncheckedAccount<'info>,
+    /// CHECK: checked by Bubblegum
+    #[account(mut)]
+    pub tree_config: UncheckedAccount<'info>,
+    /// CHECK: must be the configured badge tree
+    #[account(mut, address = badges.merkle_tree @ ErrorCode::InvalidBadgeConfig)]
+    pub merkle_tree: UncheckedAccount<'info>,
+    /// CHECK: must be the configured badge collection
+    #[account(address = badges.collection_mint @ ErrorCode::InvalidBadgeConfig)]
+    pub collection_mint: UncheckedAccount<'info>,
+    /// CHECK: checked by Bubblegum
+    #[account(mut)]
+    pub collection_metadata: UncheckedAccount<'info>,
+    /// CHECK: checked by Bubblegum
+    pub collection_edition: UncheckedAccount<'info>,
+    /// CHECK: checked by Bubblegum
+    pub bubblegum_signer: UncheckedAccount<'info>,
+    /// CHECK: checked by Bubblegum
+    pub log_wrapper: UncheckedAccount<'info>,
+    /// CHECK: checked by Bubblegum
+    pub compression_program: UncheckedAccount<'info>,
+    /// CHECK: checked by Bubblegum
+    pub token_metadata_program: UncheckedAccount<'info>,
+    /// CHECK: the Bubblegum program
+    #[account(address = mpl_bubblegum::ID)]
+    pub bubblegum_program: UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateProfile<'info> {
+    #[account(
+        init,
+        payer = wallet,
+        space = PlayerProfile::SPACE,
+        seeds = [b"profile", wallet.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, PlayerProfile>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+// Account structures
+#[derive(Accounts)]
+pub struct InitializeGame<'info> {
+    #[account(
+        mut,
+        seeds = [b"game_counter"],
+        bump
+    )]
+    pub counter: Account<'info, GameCounter>,
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + Game::SPACE,
+        seeds = [b"game", format!("game-{}", counter.count + 1).as_bytes()],
+        bump
+    )]
+    pub game: Account<'info, Game>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    /// SPL or Token-2022 mint for the entry fee; omitted for native SOL games
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    /// CHECK: PDA that owns the token vault
+    #[account(
+        seeds = [b"escrow", game.key().as_ref()],
+        bump
+    )]
+    pub escrow: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = creator,
+        seeds = [b"vault", game.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = escrow,
+        token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct JoinGame<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(
+        init_if_needed,
+        payer = player,
+        space = PlayerProfile::SPACE,
+        seeds = [b"profile", player.key().as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, PlayerProfile>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    /// The referrer's account, when joining with a referrer
+    #[account(mut)]
+    pub referral: Option<Account<'info, ReferralAccount>>,
+    /// CHECK: must not exist; spectators who bet on this game can't join it
+    #[account(
+        seeds = [b"bet", game.key().as_ref(), player.key().as_ref()],
+        bump
+    )]
+    pub prediction_bet: UncheckedAccount<'info>,
+    pub token_pot: TokenPot<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct LeaveGame<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub token_pot: TokenPot<'info>,
+}
+
+/// Token accounts for moving a game's pot; all omitted for native SOL games
+#[derive(Accounts)]
+pub struct TokenPot<'info> {
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    /// CHECK: the game's escrow PDA, verified against its stored bump
+    pub escrow: Option<UncheckedAccount<'info>>,
+    #[account(mut)]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    /// The payer's or recipient's token account
+    #[account(mut)]
+    pub token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+/// Validated view of a `TokenPot`
+struct TokenPotAccounts<'a, 'info> {
+    mint: &'a InterfaceAccount<'info, Mint>,
+    escrow: &'a UncheckedAccount<'info>,
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    token_program: &'a Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SponsorGame<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(
+        init_if_needed,
+        payer = sponsor,
+        space = Sponsorship::SPACE,
+        seeds = [b"sponsor", game.key().as_ref(), sponsor.key().as_ref()],
+        bump
+    )]
+    pub sponsorship: Account<'info, Sponsorship>,
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+    pub token_pot: TokenPot<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelGame<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RefundSponsorship<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(
+        mut,
+        seeds = [b"sponsor", game.key().as_ref(), sponsor.key().as_ref()],
+        bump
+    )]
+    pub sponsorship: Account<'info, Sponsorship>,
+    #[account(mut)]
+    pub sponsor: Signer<'info>,
+    pub token_pot: TokenPot<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureRoles<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureGame<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(season_id: u32)]
+pub struct CreateSeason<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Season::SPACE,
+        seeds = [b"season", authority.key().as_ref(), &season_id.to_le_bytes()],
+        bump
+    )]
+    pub season: Account<'info, Season>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnterSeason<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub season: Account<'info, Season>,
+    pub creator: Signer<'info>,
+    pub season_authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeSeason<'info> {
+    #[account(mut)]
+    pub season: Account<'info, Season>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimSeasonPrize<'info> {
+    #[account(mut)]
+    pub season: Account<'info, Season>,
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(tournament_id: u32)]
+pub struct CreateTournament<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Tournament::SPACE,
+        seeds = [b"tournament", authority.key().as_ref(), &tournament_id.to_le_bytes()],
+        bump
+    )]
+    pub tournament: Account<'info, Tournament>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterTournament<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+    #[account(mut)]
+    pub player: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRoundGame<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Game::SPACE,
+        seeds = [b"game", tournament.key().as_ref(), &[tournament.round], &[tournament.games.len() as u8]],
+        bump
+    )]
+    pub game: Account<'info, Game>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdvanceTournament<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+}
+
+#[derive(Accounts)]
+pub struct CancelTournament<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+}
+
+#[derive(Accounts)]
+pub struct RefundTournamentEntry<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+    #[account(mut)]
+    pub entrant: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimTournamentPrize<'info> {
+    #[account(mut)]
+    pub tournament: Account<'info, Tournament>,
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CreatePredictionPool<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(
+        init,
+        payer = creator,
+        space = PredictionPool::SPACE,
+        seeds = [b"prediction", game.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, PredictionPool>,
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct PlaceBet<'info> {
+    pub game: Account<'info, Game>,
+    #[account(
+        mut,
+        seeds = [b"prediction", game.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, PredictionPool>,
+    #[account(
+        init_if_needed,
+        payer = bettor,
+        space = PredictionBet::SPACE,
+        seeds = [b"bet", game.key().as_ref(), bettor.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, PredictionBet>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPrediction<'info> {
+    pub game: Account<'info, Game>,
+    #[account(
+        mut,
+        seeds = [b"prediction", game.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, PredictionPool>,
+    #[account(
+        mut,
+        seeds = [b"bet", game.key().as_ref(), bettor.key().as_ref()],
+        bump
+    )]
+    pub bet: Account<'info, PredictionBet>,
+    #[account(mut)]
+    pub bettor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct StartGame<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub creator: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CastVote<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub voter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevealRole<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SubmitWill<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub player: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CastVerdict<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub voter: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AdvancePhase<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct NightAction<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub actor: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PairLovers<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    /// CHECK: the SlotHashes sysvar, read by hand since it's too large to deserialize
+    #[account(address = slot_hashes::ID)]
+    pub slot_hashes: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct HoldSeance<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub medium: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct PostSeanceMessage<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub sender: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimWinnings<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(mut)]
+    pub claimer: Signer<'info>,
+    pub token_pot: TokenPot<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SettleGame<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub token_pot: TokenPot<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SweepUnclaimed<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    /// CHECK: only receives lamports; must be the configured treasury
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+    /// The game's season; required for season games when leftovers go to seasons
+    #[account(mut)]
+    pub season: Option<Account<'info, Season>>,
+    pub token_pot: TokenPot<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    /// CHECK: only receives lamports; must be the configured treasury
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+    pub token_pot: TokenPot<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(player: Pubkey)]
+pub struct RecordGameResult<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    #[account(
+        mut,
+        seeds = [b"profile", player.as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, PlayerProfile>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProfile<'info> {
+    /// CHECK: only seeds the profile's address
+    pub wallet: UncheckedAccount<'info>,
+    /// CHECK: may be too short to deserialize; the owner and seeds are checked here and
+    /// the discriminator in the instruction
+    #[account(
+        mut,
+        seeds = [b"profile", wallet.key().as_ref()],
+        bump,
+        owner = crate::ID
+    )]
+    pub profile: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SettleRatings<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    /// Required when the game was entered into a season
+    #[account(mut)]
+    pub season: Option<Account<'info, Season>>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub config: Account<'info, ProtocolConfig>,
+    /// CHECK: only receives lamports; must be the configured treasury
+    #[account(mut, address = config.treasury @ ErrorCode::InvalidTreasury)]
+    pub treasury: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateGameDetails<'info> {
+    #[account(mut)]
+    pub game: Account<'info, Game>,
+    pub authority: Signer<'info>,
+}
+
+#[account]
+pub struct GameCounter {
+    pub count: u64,
+}
+
+impl GameCounter {
+    pub const SPACE: usize = 8 + 8; // discriminator + u64
+}
+
+/// Protocol-wide settings; a singleton like `GameCounter`
+#[account]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    pub treasury: Pubkey,
+    /// Share of every pot taken as protocol fees
+    pub rake_bps: u16,
+    /// How long winners have to claim once a game finishes
+    pub claim_window_secs: u32,
+    /// Sweep unclaimed winnings of season games into the season prize pool instead of the treasury
+    pub sweep_to_season: bool,
+    /// Share of the rake from referred players that goes to their referrer
+    pub referral_share_bps: u16,
+}
+
+impl ProtocolConfig {
+    pub const SPACE: usize = 8 + 32 + 32 + 2 + 4 + 1 + 2; // discriminator + admin + treasury + rake_bps + claim_window_secs + sweep_to_season + referral_share_bps
+    pub const MAX_RAKE_BPS: u16 = 2_000;
+}
+
+/// Lifetime record for a wallet, shared across games
+#[account]
+pub struct PlayerProfile {
+    pub wallet: Pubkey,
+    pub rating: u32,
+    pub ranked_games: u32,
+    pub wins_as_town: u32,
+    pub wins_as_mafia: u32,
+    pub games_joined: u32,
+    pub games_finished: u32,
+    pub times_lynched: u32,
+    pub correct_mafia_votes: u32,
+    pub successful_heals: u32,
+    pub sol_won: u64,
+    /// Who referred this wallet, fixed on its first join
+    pub referrer: Option<Pubkey>,
+    // Fields below were added after profiles went live; `migrate_profile` grows older accounts to fit
+    pub doctor_survivals: u32,
+    pub mafia_lynches: u32,
+}
+
+impl PlayerProfile {
+    pub const SPACE: usize = 8 + 32 + 4 + 4 + (4 * 7) + 8 + 33 + 4 + 4; // discriminator + wallet + rating + ranked_games + counters + sol_won + referrer + doctor_survivals + mafia_lynches
+    pub const STARTING_RATING: u32 = 1200;
+    pub const RATING_K: u32 = 32;
+}
+
+/// Where achievement badges are minted: a compressed NFT tree and its collection
+#[account]
+pub struct BadgeConfig {
+    pub merkle_tree: Pubkey,
+    pub collection_mint: Pubkey,
+    /// Badge metadata lives at `{uri_base}/{slug}.json`
+    pub uri_base: String,
+}
+
+impl BadgeConfig {
+    pub const MAX_URI_BASE_LEN: usize = 100;
+    pub const SPACE: usize = 8 + 32 + 32 + 4 + BadgeConfig::MAX_URI_BASE_LEN; // discriminator + merkle_tree + collection_mint + uri_base
+    pub const SYMBOL: &'static str = "MAFIA";
+}
+
+/// Proof that a wallet has minted an achievement's badge
+#[account]
+pub struct AchievementReceipt {
+    pub wallet: Pubkey,
+    pub achievement: Achievement,
+    pub minted_at: i64,
+}
+
+impl AchievementReceipt {
+    pub const SPACE: usize = 8 + 32 + 1 + 8; // discriminator + wallet + achievement + minted_at
+}
+
+/// Milestones read from a wallet's lifetime profile
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum Achievement {
+    FirstMafiaWin,
+    SurvivedAsDoctor,
+    TenCorrectLynches,
+}
+
+impl Achievement {
+    pub fn is_earned(&self, profile: &PlayerProfile) -> bool {
+        match self {
+            Achievement::FirstMafiaWin => profile.wins_as_mafia >= 1,
+            Achievement::SurvivedAsDoctor => profile.doctor_survivals >= 1,
+            Achievement::TenCorrectLynches => profile.mafia_lynches >= 10,
+        }
+    }
+    
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::FirstMafiaWin => "First Win as Mafia",
+            Achievement::SurvivedAsDoctor => "Survived as Doctor",
+            Achievement::TenCorrectLynches => "10 Correct Lynches",
+        }
+    }
+    
+    pub fn slug(&self) -> &'static str {
+        match self {
+            Achievement::FirstMafiaWin => "first-mafia-win",
+            Achievement::SurvivedAsDoctor => "survived-as-doctor",
+            Achievement::TenCorrectLynches => "ten-correct-lynches",
+        }
+    }
+}
+
+/// Rewards a referrer has earned from the rake of the players they brought in
+#[account]
+pub struct ReferralAccount {
+    pub referrer: Pubkey,
+    pub referred_count: u32,
+    /// Earned but not yet claimed; held as lamports in this account
+    pub pending: u64,
+    pub total_earned: u64,
+}
+
+impl ReferralAccount {
+    pub const SPACE: usize = 8 + 32 + 4 + 8 + 8; // discriminator + referrer + referred_count + pending + total_earned
+}
+
+/// A ranked season with a bounded leaderboard and a prize pool for its top players
+#[account]
+pub struct Season {
+    pub season_id: u32,
+    pub authority: Pubkey,
+    pub start_time: i64,
+    pub end_time: i64,
+    /// Share of each season game's entry fees that goes to the prize pool
+    pub prize_share_bps: u16,
+    pub prize_pool: u64,
+    /// The pool as it stood when the season was frozen; prizes are paid from this
+    pub frozen_pool: u64,
+    pub frozen: bool,
+    /// Best ratings first
+    pub leaderboard: Vec<LeaderboardEntry>,
+}
+
+impl Season {
+    pub const MAX_LEADERBOARD: usize = 10;
+    pub const SPACE: usize = 8 + 4 + 32 + 8 + 8 + 2 + 8 + 8 + 1 +
+                             4 + (Season::MAX_LEADERBOARD * (32 + 4 + 1)); // leaderboard: Vec<LeaderboardEntry>
+    
+    /// Updates a player's standing, keeping only the top entries
+    pub fn record_rating(&mut self, player: Pubkey, rating: u32) {
+        self.leaderboard.retain(|e| e.player != player);
+        let position = self.leaderboard.iter().position(|e| e.rating < rating)
+            .unwrap_or(self.leaderboard.len());
+        self.leaderboard.insert(position, LeaderboardEntry { player, rating, claimed: false });
+        self.leaderboard.truncate(Season::MAX_LEADERBOARD);
+    }
+    
+    /// Prizes are weighted by rank: first place gets N parts, last place gets one
+    pub fn prize_for_rank(&self, rank: usize) -> u64 {
+        let places = self.leaderboard.len() as u64;
+        let total_weight = places * (places + 1) / 2;
+        (self.frozen_pool as u128 * (places - rank as u64) as u128 / total_weight as u128) as u64
+    }
+}
+
+/// One sponsor's total bonus for a game
+#[account]
+pub struct Sponsorship {
+    pub game: Pubkey,
+    pub sponsor: Pubkey,
+    pub amount: u64,
+    pub refunded: bool,
+}
+
+impl Sponsorship {
+    pub const SPACE: usize = 8 + 32 + 32 + 8 + 1; // discriminator + game + sponsor + amount + refunded
+}
+
+/// Spectator bets on a game's outcome, settled parimutuel from `game.winner`
+#[account]
+pub struct PredictionPool {
+    pub game: Pubkey,
+    /// Betting closes when this day begins; 0 closes it when the game starts
+    pub close_day: u16,
+    pub town_total: u64,
+    pub mafia_total: u64,
+}
+
+impl PredictionPool {
+    pub const SPACE: usize = 8 + 32 + 2 + 8 + 8; // discriminator + game + close_day + town_total + mafia_total
+    
+    pub fn is_open(&self, game: &Game) -> bool {
+        match game.state {
+            GameState::WaitingForPlayers => true,
+            GameState::Active => game.day_count < self.close_day,
+            GameState::Finished | GameState::Cancelled => false,
+        }
+    }
+    
+    /// What a bet returns once the game is over. If nobody backed the winning side,
+    /// or neither side won, every stake is refunded.
+    pub fn payout(&self, winner: Option<&Winner>, bet: &PredictionBet) -> u64 {
+        let winning_total = match winner {
+            Some(Winner::Town) => self.town_total,
+            Some(Winner::Mafia) => self.mafia_total,
+            Some(Winner::Lovers) | None => 0,
+        };
+        if winning_total == 0 {
+            return bet.amount;
+        }
+        
+        let won = matches!(
+            (winner, bet.side),
+            (Some(Winner::Town), PredictionSide::Town) | (Some(Winner::Mafia), PredictionSide::Mafia)
+        );
+        if !won {
+            return 0;
+        }
+        let total = (self.town_total + self.mafia_total) as u128;
+        (bet.amount as u128 * total / winning_total as u128) as u64
+    }
+}
+
+#[account]
+pub struct PredictionBet {
+    pub bettor: Pubkey,
+    pub side: PredictionSide,
+    pub amount: u64,
+    pub claimed: bool,
+}
+
+impl PredictionBet {
+    pub const SPACE: usize = 8 + 32 + 1 + 8 + 1; // discriminator + bettor + side + amount + claimed
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum PredictionSide {
+    Town,
+    Mafia,
+}
+
+/// A bracket of program-created games, paid from the entrants' buy-ins by final placement
+#[account]
+pub struct Tournament {
+    pub tournament_id: u32,
+    pub authority: Pubkey,
+    pub buy_in: u64,
+    pub players_per_game: u8,
+    pub registration_end: i64,
+    pub state: TournamentState,
+    /// Current round, starting at 1
+    pub round: u8,
+    pub pool: u64,
+    /// Share of the pool for each placement, champions first
+    pub prize_split_bps: Vec<u16>,
+    pub entrants: Vec<TournamentEntrant>,
+    /// The current round's games, in creation order
+    pub games: Vec<Pubkey>,
+    /// When the current round's unfinished games stop holding up the bracket
+    pub round_deadline: i64,
+}
+
+impl Tournament {
+    pub const MAX_ENTRANTS: usize = 40;
+    pub const MAX_PLACES: usize = 4;
+    pub const MAX_GAMES_PER_ROUND: usize = 5;
+    // Rounds split players evenly across games, so anything smaller could leave a game under the minimum
+    pub const MIN_PLAYERS_PER_GAME: u8 = 8;
+    pub const MAX_PLAYERS_PER_GAME: u8 = 20;
+    pub const ROUND_DURATION: i64 = 3 * 24 * 60 * 60; // 3 days
+    pub const SPACE: usize = 8 + 4 + 32 + 8 + 1 + 8 + 1 + 1 + 8 +
+                             4 + (Tournament::MAX_PLACES * 2) + // prize_split_bps: Vec<u16>
+                             4 + (Tournament::MAX_ENTRANTS * (32 + 2 + 1)) + // entrants: Vec<TournamentEntrant>
+                             4 + (Tournament::MAX_GAMES_PER_ROUND * 32) + // games: Vec<Pubkey>
+                             8; // round_deadline
+    
+    /// How many games the current round is split into
+    pub fn round_game_count(&self) -> usize {
+        let alive = self.entrants.iter().filter(|e| e.eliminated_round.is_none()).count();
+        alive.div_ceil(self.players_per_game as usize)
+    }
+    
+    /// Knocks out the losers of one of the current round's games; a game still
+    /// running past the round deadline counts as a draw, so everyone in it advances
+    pub fn apply_round_game(&mut self, game: &Game, now: i64) -> Result<()> {
+        if game.state != GameState::Finished {
+            require!(now >= self.round_deadline, ErrorCode::RoundNotFinished);
+            return Ok(());
+        }
+        let winner = game.winner.clone().ok_or(ErrorCode::NoWinner)?;
+        
+        for player in game.players.iter().filter(|p| !on_winning_side(p, &winner)) {
+            if let Some(entrant) = self.entrants.iter_mut().find(|e| e.player == player.address) {
+                entrant.eliminated_round = Some(self.round);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Placement 0 is everyone who was never knocked out, 1 is those who fell in the
+    /// final round, and so on; each placement splits its share of the pool equally
+    fn placement(&self, entrant: &TournamentEntrant) -> usize {
+        entrant.eliminated_round.map_or(0, |round| (self.round - round) as usize + 1)
+    }
+    
+    /// Prize owed to an entrant; shares of placements nobody reached go to the champions,
+    /// and the first champion also takes the lamports left over by rounding down
+    pub fn prize_for(&self, index: usize) -> u64 {
+        let placement = self.placement(&self.entrants[index]);
+        let mut counts = vec![0u64; self.prize_split_bps.len()];
+        for entrant in &self.entrants {
+            if let Some(count) = counts.get_mut(self.placement(entrant)) {
+                *count += 1;
+            }
+        }
+        
+        let mut shares: Vec<u64> = self.prize_split_bps.iter()
+            .map(|bps| (self.pool as u128 * *bps as u128 / 10_000) as u64)
+            .collect();
+        shares[0] += shares.iter().zip(counts.iter())
+            .filter(|(_, count)| **count == 0)
+            .map(|(share, _)| *share)
+            .sum::<u64>();
+        
+        let Some(&share) = shares.get(placement) else {
+            return 0;
+        };
+        let mut prize = share / counts[placement].max(1);
+        if placement == 0 && self.entrants.iter().position(|e| e.eliminated_round.is_none()) == Some(index) {
+            let paid = shares.iter().zip(counts.iter())
+                .filter(|(_, count)| **count > 0)
+                .map(|(share, count)| share / count * count)
+                .sum::<u64>();
+            prize += self.pool - paid;
+        }
+        prize
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TournamentEntrant {
+    pub player: Pubkey,
+    /// Round the entrant was knocked out in; `None` while still in
+    pub eliminated_round: Option<u8>,
+    /// Set once the entrant's prize, or their refund if cancelled, has been paid
+    pub paid_out: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum TournamentState {
+    Registration,
+    InProgress,
+    Finished,
+    /// Registration closed without enough entrants; buy-ins are refundable
+    Cancelled,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LeaderboardEntry {
+    pub player: Pubkey,
+    pub rating: u32,
+    pub claimed: bool,
+}
+
+// Data structures
+#[account]
+pub struct Game {
+    pub game_id: String,
+    pub name: String,
+    pub creator: Pubkey,
+    pub max_players: u8,
+    pub entry_fee: u64,
+    pub is_public: bool,
+    pub state: GameState,
+    pub current_phase: GamePhase,
+    pub day_count: u16,
+    pub created_at: i64,
+    pub phase_start_time: i64,
+    pub phase_end_time: i64,
+    pub players: Vec<Player>,
+    pub roles: Vec<Role>,
+    pub votes: Vec<Vote>,
+    pub eliminated_players: Vec<Pubkey>,
+    pub winner: Option<Winner>,
+    pub settings: GameSettings,
+    pub night_actions: Vec<NightActionRecord>,
+    pub seances: Vec<Seance>,
+    /// Tonight's Mafia target once every Mafia member has picked, so the Witch can
+    /// decide whether to heal it. Like the rest of the account it is public; clients
+    /// should only show it to the Witch, but anyone can read it.
+    pub mafia_target: Option<Pubkey>,
+    pub on_trial: Option<Pubkey>,
+    pub mode: GameMode,
+    pub ratings_settled: bool,
+    pub season: Option<Pubkey>,
+    pub season_share_bps: u16,
+    pub rake_bps: u16,
+    pub claim_window_secs: u32,
+    pub referral_share_bps: u16,
+    /// Unclaimed winnings can be swept after this; set when the game finishes
+    pub claim_deadline: i64,
+    pub fees_collected: bool,
+    /// Entry fees actually received, net of any token transfer fees
+    pub pot: u64,
+    /// Sponsor bonuses on top of the pot, also net of transfer fees
+    pub sponsor_pool: u64,
+    /// Entry fee mint; `None` for native SOL
+    pub mint: Option<Pubkey>,
+    pub escrow_bump: u8,
+    /// Slot whose hash will pick the Lovers; set at the start and cleared once they're paired
+    pub lovers_seed_slot: Option<u64>,
+    /// Set once the creator has rewritten the game through `update_game_details`
+    pub creator_override: bool,
+    /// Set once a prediction pool is opened on the game
+    pub has_prediction_pool: bool,
+}
+
+impl Game {
+    pub const SPACE: usize = 32 + 32 + 1 + 8 + 1 + 1 + 2 + 8 + 8 + 8 + 
+                             4 + (20 * (32 + 1 + 1 + 33 + 8 + RoleState::SPACE + Modifier::SPACE + Will::SPACE + 2 + PlayerStats::SPACE + 33)) + // players: Vec<Player> (max 20)
+                             4 + 20 + // roles: Vec<Role> (max 20)
+                             4 + (20 * (32 + 32 + 8)) + // votes: Vec<Vote> (max 20)
+                             4 + (20 * 32) + // eliminated_players: Vec<Pubkey> (max 20)
+                             2 + 1 + // winner: Option<Winner> + is_public: bool
+                             GameSettings::SPACE +
+                             4 + (21 * NightActionRecord::SPACE) + // night_actions: Vec<NightActionRecord> (one per player, plus the Witch's second potion)
+                             4 + (Game::MAX_SEANCES * Seance::SPACE) + // seances: Vec<Seance>
+                             1 + 32 + // mafia_target: Option<Pubkey>
+                             1 + 32 + // on_trial: Option<Pubkey>
+                             1 + // mode: GameMode
+                             1 + // ratings_settled: bool
+                             1 + 32 + 2 + // season: Option<Pubkey> + season_share_bps
+                             2 + 4 + 2 + 8 + 1 + // rake_bps + claim_window_secs + referral_share_bps + claim_deadline + fees_collected
+                             8 + 8 + 1 + 32 + 1 + // pot + sponsor_pool + mint: Option<Pubkey> + escrow_bump
+                             1 + 8 + 1 + 1; // lovers_seed_slot: Option<u64> + creator_override + has_prediction_pool
+    
+    pub const MAX_SEANCES: usize = 2;
+    pub const LOVERS_SEED_DELAY_SLOTS: u64 = 4;
+}
+
+/// Per-game rule knobs, set by the creator before the game starts
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct GameSettings {
+    pub vigilante_shots: u8,
+    /// Day vote weight of a Mayor who has revealed
+    pub mayor_vote_weight: u8,
+    pub lovers_enabled: bool,
+    /// Replaces the free-for-all Day with Discussion, Nomination, Defense and Judgment
+    pub structured_day: bool,
+    pub discussion_secs: u32,
+    pub nomination_secs: u32,
+    pub defense_secs: u32,
+    pub judgment_secs: u32,
+    /// What death events reveal; roles stay readable in account state either way
+    pub reveal_on_death: RevealOnDeath,
+    /// Day and Night deadlines; zero leaves the phase open until it is advanced
+    pub day_secs: u32,
+    pub night_secs: u32,
+    /// Finished ranked games update every player's rating
+    pub ranked: bool,
+    pub payout_rule: PayoutRule,
+}
+
+impl GameSettings {
+    pub const SPACE: usize = 1 + 1 + 1 + 1 + (4 * 4) + 1 + (2 * 4) + 1 + PayoutRule::SPACE; // vigilante_shots + mayor_vote_weight + lovers_enabled + structured_day + timers + reveal_on_death + day/night deadlines + ranked + payout_rule
+    
+    pub const SPEED_MAX_PHASE_SECS: u32 = 120;
+    pub const MIN_PLAYERS: usize = 4;
+    pub const RANKED_MIN_PLAYERS: usize = 7;
+    
+    /// Preset rules for Speed Mafia: short Day and Night deadlines and no structured day
+    pub fn speed() -> Self {
+        Self {
+            day_secs: 90,
+            night_secs: 30,
+            ..Self::default()
+        }
+    }
+    
+    /// How long a timed phase lasts before it can be advanced
+    pub fn phase_duration(&self, phase: GamePhase) -> Option<u32> {
+        match phase {
+            GamePhase::Discussion => Some(self.discussion_secs),
+            GamePhase::Nomination => Some(self.nomination_secs),
+            GamePhase::Defense => Some(self.defense_secs),
+            GamePhase::Judgment => Some(self.judgment_secs),
+            GamePhase::Day if self.day_secs > 0 => Some(self.day_secs),
+            GamePhase::Night if self.night_secs > 0 => Some(self.night_secs),
+            GamePhase::Lobby | GamePhase::Day | GamePhase::Night => None,
+        }
+    }
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            vigilante_shots: 1,
+            mayor_vote_weight: 2,
+            lovers_enabled: false,
+            structured_day: false,
+            discussion_secs: 120,
+            nomination_secs: 60,
+            defense_secs: 30,
+            judgment_secs: 30,
+            reveal_on_death: RevealOnDeath::FullRole,
+            day_secs: 0,
+            night_secs: 0,
+            ranked: false,
+            payout_rule: PayoutRule::SurvivorsOnly,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub struct Player {
+    pub address: Pubkey,
+    pub is_alive: bool,
+    pub role: Role,
+    pub vote_target: Option<Pubkey>,
+    pub joined_at: i64,
+    pub role_state: RoleState,
+    pub modifier: Modifier,
+    pub will: Will,
+    /// Guilty (true) or innocent (false) during a trial's judgment
+    pub verdict: Option<bool>,
+    pub stats: PlayerStats,
+    /// Carried over from the player's profile when they join
+    pub referrer: Option<Pubkey>,
+}
+
+impl Player {
+    pub fn new(address: Pubkey, joined_at: i64) -> Self {
+        Self {
+            address,
+            is_alive: true,
+            role: Role::Unknown,
+            vote_target: None,
+            joined_at,
+            role_state: RoleState::default(),
+            modifier: Modifier::None,
+            will: Will::None,
+            verdict: None,
+            stats: PlayerStats::default(),
+            referrer: None,
+        }
+    }
+}
+
+/// Per-game tallies that feed the player's lifetime profile
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Default)]
+pub struct PlayerStats {
+    pub lynched: bool,
+    pub correct_votes: u8,
+    /// Day votes that ended in a Mafia member being lynched
+    pub mafia_lynches: u8,
+    pub successful_heals: u8,
+    pub result_recorded: bool,
+    /// Winnings already sent, by claim or settlement
+    pub paid: bool,
+}
+
+impl PlayerStats {
+    pub const SPACE: usize = 1 + 1 + 1 + 1 + 1 + 1;
+}
+
+/// Secret modifiers layered on top of a player's role
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum Modifier {
+    None,
+    /// Dies together with `partner`, and wins with them if they are the last two alive
+    Lover { partner: Pubkey },
+}
+
+impl Modifier {
+    pub const SPACE: usize = 1 + 32; // variant + largest payload
+}
+
+/// Last words, published when the player dies
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum Will {
+    None,
+    Text(String),
+    /// Hash of content kept off-chain
+    Hash([u8; 32]),
+}
+
+impl Will {
+    pub const MAX_TEXT_LEN: usize = 64;
+    pub const SPACE: usize = 1 + 4 + Will::MAX_TEXT_LEN; // variant + largest payload
+}
+
+/// Role-specific resources and status tracked per player
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Default)]
+pub struct RoleState {
+    pub shots_remaining: u8,
+    /// Day count of the night on which a misfiring Vigilante dies of guilt
+    pub guilt_night: Option<u16>,
+    /// Whether the player has publicly revealed their role
+    pub revealed: bool,
+    pub heal_potion_used: bool,
+    pub poison_potion_used: bool,
+}
+
+impl RoleState {
+    pub const SPACE: usize = 1 + (1 + 2) + 1 + 1 + 1; // shots_remaining + guilt_night + revealed + potions
+}
+
+/// A player's full card, published when the game ends
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PlayerReveal {
+    pub address: Pubkey,
+    pub role: Role,
+    pub is_alive: bool,
+    pub modifier: Modifier,
+    pub role_state: RoleState,
+}
+
+/// A night action waiting to be resolved when the night ends
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NightActionRecord {
+    pub actor: Pubkey,
+    pub action_type: NightActionType,
+    pub target: Pubkey,
+}
+
+impl NightActionRecord {
+    pub const SPACE: usize = 32 + 1 + 32; // actor + action_type + target
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NightDeath {
+    pub player: Pubkey,
+    pub cause: DeathCause,
+    /// The player whose action caused the death, or the Lover whose death did
+    pub killer: Pubkey,
+}
+
+/// A night death as announced; the cause and killer are only given when roles are revealed in full
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct NightDeathReport {
+    pub player: Pubkey,
+    pub cause: Option<DeathCause>,
+    pub killer: Option<Pubkey>,
+}
+
+/// A one-night channel between a Medium and a dead player. Messages are
+/// exchanged off-chain, encrypted to the participants; only their hashes are
+/// logged here so either side can later prove what was said.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Seance {
+    pub medium: Pubkey,
+    pub spirit: Pubkey,
+    pub day_count: u16,
+    pub message_hashes: Vec<[u8; 32]>,
+}
+
+impl Seance {
+    pub const MAX_MESSAGES: usize = 16;
+    pub const SPACE: usize = 32 + 32 + 2 + 4 + (Seance::MAX_MESSAGES * 32);
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RatingChange {
+    pub player: Pubkey,
+    pub old_rating: u32,
+    pub new_rating: u32,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct Vote {
+    pub voter: Pubkey,
+    pub target: Pubkey,
+    pub timestamp: i64,
+}
+
+// Enums
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum GameMode {
+    Classic,
+    /// Short deadlines, phases that end once everyone has acted, and a reduced role deck
+    Speed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum GameState {
+    WaitingForPlayers,
+    Active,
+    Finished,
+    Cancelled,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum GamePhase {
+    Lobby,
+    Day,
+    Night,
+    Discussion,
+    Nomination,
+    Defense,
+    Judgment,
+}
+
+impl GamePhase {
+    /// Day phases, including the sub-phases of a structured day
+    pub fn is_day(&self) -> bool {
+        matches!(
+            self,
+            GamePhase::Day | GamePhase::Discussion | GamePhase::Nomination | GamePhase::Defense | GamePhase::Judgment
+        )
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum Role {
+    Unknown,
+    Mafia,
+    Detective,
+    Doctor,
+    Civilian,
+    Godfather,
+    Vigilante,
+    Escort,
+    Mayor,
+    Bodyguard,
+    Medium,
+    Witch,
+}
+
+impl Role {
+    /// Roles that count towards the Mafia for the win condition and the night kill
+    pub fn is_mafia(&self) -> bool {
+        matches!(self, Role::Mafia | Role::Godfather)
+    }
+
+    pub fn alignment(&self) -> Alignment {
+        if self.is_mafia() {
+            Alignment::Mafia
+        } else {
+            Alignment::Town
+        }
+    }
+
+    /// Roles that act at night through `night_action`
+    pub fn has_night_action(&self) -> bool {
+        matches!(
+            self,
+            Role::Mafia
+                | Role::Godfather
+                | Role::Detective
+                | Role::Doctor
+                | Role::Vigilante
+                | Role::Escort
+                | Role::Bodyguard
+                | Role::Witch
+        )
+    }
+
+    /// The reduced deck Speed Mafia is played with
+    pub fn in_speed_deck(&self) -> bool {
+        matches!(self, Role::Mafia | Role::Detective | Role::Doctor | Role::Civilian)
+    }
+
+    /// Roles that survive a Bodyguard's counterattack
+    pub fn is_night_immune(&self) -> bool {
+        matches!(self, Role::Godfather)
+    }
+
+    /// What a Detective sees when investigating this role
+    pub fn investigation_result(&self) -> InvestigationResult {
+        match self {
+            Role::Mafia => InvestigationResult::Mafia,
+            // The Godfather plays for the Mafia but reads as innocent
+            Role::Godfather => InvestigationResult::NotMafia,
+            Role::Unknown
+            | Role::Detective
+            | Role::Doctor
+            | Role::Civilian
+            | Role::Vigilante
+            | Role::Escort
+            | Role::Mayor
+            | Role::Bodyguard
+            | Role::Medium
+            | Role::Witch => InvestigationResult::NotMafia,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum Alignment {
+    Town,
+    Mafia,
+}
+
+/// How much the program's death announcements (`PlayerEliminated` and `NightResolved`)
+/// say about a player. This only governs events. Roles are dealt on-chain and stored
+/// in plain text in the game account, since the program needs them to resolve the
+/// game, so anyone reading account state sees every role whatever this is set to.
+/// Every card is published in `GameEnded` once the game is over.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum RevealOnDeath {
+    FullRole,
+    AlignmentOnly,
+    Hidden,
+}
+
+/// How the prize pool is split among the winning side. Rounding dust goes
+/// to the protocol treasury when fees are collected.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum PayoutRule {
+    /// Equal split across the winning faction, dead or alive
+    WholeFaction,
+    /// Equal split among winners who survived
+    SurvivorsOnly,
+    /// Whole faction, with each survivor taking `survivor_weight` shares to a dead teammate's one
+    SurvivorBonus { survivor_weight: u8 },
+    /// Whole faction, plus `bonus_bps` of the prize pool to the winner with the most correct votes
+    MvpBonus { bonus_bps: u16 },
+}
+
+impl PayoutRule {
+    pub const SPACE: usize = 1 + 2; // variant + largest payload
+    pub const MAX_MVP_BONUS_BPS: u16 = 5_000;
+    
+    pub fn is_valid(&self) -> bool {
+        match self {
+            PayoutRule::SurvivorBonus { survivor_weight } => *survivor_weight > 0,
+            PayoutRule::MvpBonus { bonus_bps } => *bonus_bps <= Self::MAX_MVP_BONUS_BPS,
+            PayoutRule::WholeFaction | PayoutRule::SurvivorsOnly => true,
+        }
+    }
+    
+    /// How many shares of the pool a player gets
+    pub fn shares(&self, player: &Player, winner: &Winner) -> u64 {
+        if !on_winning_side(player, winner) {
+            return 0;
+        }
+        match self {
+            PayoutRule::WholeFaction | PayoutRule::MvpBonus { .. } => 1,
+            PayoutRule::SurvivorsOnly => player.is_alive as u64,
+            PayoutRule::SurvivorBonus { survivor_weight } => {
+                if player.is_alive { *survivor_weight as u64 } else { 1 }
+            },
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum DeathReveal {
+    Role(Role),
+    Alignment(Alignment),
+    Hidden,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum InvestigationResult {
+    Mafia,
+    NotMafia,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
+pub enum Winner {
+    Mafia,
+    Town,
+    Lovers,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum DeathCause {
+    MafiaKill,
+    VigilanteShot,
+    DiedGuarding,
+    BodyguardCounter,
+    Heartbreak,
+    Poison,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum NightActionType {
+    MafiaKill,
+    DetectiveInvestigate,
+    DoctorHeal,
+    VigilanteShoot,
+    Roleblock,
+    Guard,
+    WitchHeal,
+    WitchPoison,
+}
+
+// Events
+#[event]
+pub struct GameCreated {
+    pub game_id: String,
+    pub name: String,
+    pub creator: Pubkey,
+    pub max_players: u8,
+    pub entry_fee: u64,
+    pub mint: Option<Pubkey>,
+    pub is_public: bool,
+}
+
+#[event]
+pub struct PlayerJoined {
+    pub game_id: String,
+    pub player: Pubkey,
+    pub player_count: u8,
+}
+
+#[event]
+pub struct PlayerLeft {
+    pub game_id: String,
+    pub player: Pubkey,
+    pub refund: u64,
+}
+
+#[event]
+pub struct RolesConfigured {
+    pub game_id: String,
+    pub roles: Vec<Role>,
+}
+
+#[event]
+pub struct GameModeSet {
+    pub game_id: String,
+    pub mode: GameMode,
+}
+
+#[event]
+pub struct GameConfigured {
+    pub game_id: String,
+    pub settings: GameSettings,
+}
+
+#[event]
+pub struct GameStarted {
+    pub game_id: String,
+    pub player_count: u8,
+    pub day_count: u16,
 }
 
-// Helper functions
-fn process_day_votes(game: &mut Game) -> Result<Option<Pubkey>> {
-    let mut vote_counts: HashMap<Pubkey, u32> = HashMap::new();
-    
-    for vote in &game.votes {
-        *vote_counts.entry(vote.target).or_insert(0) += 1;
-    }
-    
-    if let Some((most_voted, _)) = vote_counts.iter().max_by_key(|(_, count)| *count) {
-        let target_index = game.players.iter().position(|p| p.address == *most_voted).unwrap();
-        game.players[target_index].is_alive = false;
-        game.eliminated_players.push(*most_voted);
-        return Ok(Some(*most_voted));
-    }
-    
-    Ok(None)
+/// The pair itself stays secret; only the Lovers' modifiers say who they are
+#[event]
+pub struct LoversPaired {
+    pub game_id: String,
 }
 
-fn check_win_condition(game: &Game) -> Result<Option<Winner>> {
-    let alive_mafia = game.players.iter().filter(|p| p.is_alive && p.role == Role::Mafia).count();
-    let alive_town = game.players.iter().filter(|p| p.is_alive && p.role != Role::Mafia).count();
-    
-    if alive_mafia == 0 {
-        return Ok(Some(Winner::Town));
-    }
-    
-    if alive_mafia >= alive_town {
-        return Ok(Some(Winner::Mafia));
-    }
-    
-    Ok(None)
+#[event]
+pub struct VoteCast {
+    pub game_id: String,
+    pub voter: Pubkey,
+    pub target: Pubkey,
+    pub total_votes: u8,
 }
 
-#[derive(Accounts)]
-pub struct InitializeCounter<'info> {
-    #[account(
-        init,
-        payer = payer,
-        space = GameCounter::SPACE,
-        seeds = [b"game_counter"],
-        bump
-    )]
-    pub counter: Account<'info, GameCounter>,
-    #[account(mut)]
-    pub payer: Signer<'info>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct RoleRevealed {
+    pub game_id: String,
+    pub player: Pubkey,
+    pub role: Role,
 }
 
-// Account structures
-#[derive(Accounts)]
-pub struct InitializeGame<'info> {
-    #[account(
-        mut,
-        seeds = [b"game_counter"],
-        bump
-    )]
-    pub counter: Account<'info, GameCounter>,
-    #[account(
-        init,
-        payer = creator,
-        space = 8 + Game::SPACE,
-        seeds = [b"game", format!("game-{}", counter.count + 1).as_bytes()],
-        bump
-    )]
-    pub game: Account<'info, Game>,
-    #[account(mut)]
-    pub creator: Signer<'info>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct WillSubmitted {
+    pub game_id: String,
+    pub player: Pubkey,
 }
 
-#[derive(Accounts)]
-pub struct JoinGame<'info> {
-    #[account(mut)]
-    pub game: Account<'info, Game>,
-    #[account(mut)]
-    pub player: Signer<'info>,
-    pub system_program: Program<'info, System>,
+#[event]
+pub struct WillRevealed {
+    pub game_id: String,
+    pub player: Pubkey,
+    pub will: Will,
 }
 
-#[derive(Accounts)]
-pub struct StartGame<'info> {
-    #[account(mut)]
-    pub game: Account<'info, Game>,
-    pub creator: Signer<'info>,
+#[event]
+pub struct TrialStarted {
+    pub game_id: String,
+    pub accused: Pubkey,
+    pub day_count: u16,
 }
 
-#[derive(Accounts)]
-pub struct CastVote<'info> {
-    #[account(mut)]
-    pub game: Account<'info, Game>,
-    pub voter: Signer<'info>,
+#[event]
+pub struct VerdictCast {
+    pub game_id: String,
+    pub voter: Pubkey,
+    pub guilty: bool,
 }
 
-#[derive(Accounts)]
-pub struct AdvancePhase<'info> {
-    #[account(mut)]
-    pub game: Account<'info, Game>,
-    pub authority: Signer<'info>,
+#[event]
+pub struct TrialVerdict {
+    pub game_id: String,
+    pub accused: Pubkey,
+    pub guilty: bool,
+    pub guilty_votes: u32,
+    pub innocent_votes: u32,
+    pub day_count: u16,
 }
 
-#[derive(Accounts)]
-pub struct NightAction<'info> {
-    #[account(mut)]
-    pub game: Account<'info, Game>,
-    pub actor: Signer<'info>,
+#[event]
+pub struct PhaseChanged {
+    pub game_id: String,
+    pub new_phase: GamePhase,
+    pub day_count: u16,
 }
 
-#[derive(Accounts)]
-pub struct ClaimWinnings<'info> {
-    #[account(mut)]
-    pub game: Account<'info, Game>,
-    #[account(mut)]
-    pub claimer: Signer<'info>,
+#[event]
+pub struct PlayerEliminated {
+    pub game_id: String,
+    pub player: Pubkey,
+    pub phase: GamePhase,
+    pub day_count: u16,
+    pub revealed: DeathReveal,
 }
 
-#[derive(Accounts)]
-pub struct UpdateGameDetails<'info> {
-    #[account(mut)]
-    pub game: Account<'info, Game>,
-    pub authority: Signer<'info>,
+#[event]
+pub struct GameEnded {
+    pub game_id: String,
+    pub winner: Winner,
+    pub day_count: u16,
+    pub players: Vec<PlayerReveal>,
 }
 
-#[account]
-pub struct GameCounter {
-    pub count: u64,
+#[event]
+pub struct NightActionPerformed {
+    pub game_id: String,
+    pub actor: Pubkey,
+    pub action_type: NightActionType,
+    pub target: Option<Pubkey>,
 }
 
-impl GameCounter {
-    pub const SPACE: usize = 8 + 8; // discriminator + u64
+#[event]
+pub struct PlayerInvestigated {
+    pub game_id: String,
+    pub detective: Pubkey,
+    pub target: Pubkey,
+    pub result: InvestigationResult,
 }
 
-// Data structures
-#[account]
-pub struct Game {
+#[event]
+pub struct MafiaTargetSettled {
     pub game_id: String,
-    pub name: String,
-    pub creator: Pubkey,
-    pub max_players: u8,
-    pub entry_fee: u64,
-    pub is_public: bool,
-    pub state: GameState,
-    pub current_phase: GamePhase,
     pub day_count: u16,
-    pub created_at: i64,
-    pub phase_start_time: i64,
-    pub phase_end_time: i64,
-    pub players: Vec<Player>,
-    pub roles: Vec<Role>,
-    pub votes: Vec<Vote>,
-    pub eliminated_players: Vec<Pubkey>,
-    pub winner: Option<Winner>,
 }
 
-impl Game {
-    pub const SPACE: usize = 32 + 32 + 1 + 8 + 1 + 1 + 2 + 8 + 8 + 8 + 
-                             4 + (20 * (32 + 1 + 1 + 33 + 8)) + // players: Vec<Player> (max 20)
-                             4 + (20 * 1) + // roles: Vec<Role> (max 20)
-                             4 + (20 * (32 + 32 + 8)) + // votes: Vec<Vote> (max 20)
-                             4 + (20 * 32) + // eliminated_players: Vec<Pubkey> (max 20)
-                             2 + 1; // winner: Option<Winner> + is_public: bool
+#[event]
+pub struct NightResolved {
+    pub game_id: String,
+    pub day_count: u16,
+    /// Players whose night action was roleblocked
+    pub blocked: Vec<Pubkey>,
+    pub deaths: Vec<NightDeathReport>,
+}
+
+#[event]
+pub struct SeanceOpened {
+    pub game_id: String,
+    pub medium: Pubkey,
+    pub spirit: Pubkey,
+    pub day_count: u16,
+}
+
+#[event]
+pub struct SeanceMessagePosted {
+    pub game_id: String,
+    pub medium: Pubkey,
+    pub sender: Pubkey,
+    pub message_hash: [u8; 32],
+    pub day_count: u16,
+}
+
+#[event]
+pub struct WinningsClaimed {
+    pub game_id: String,
+    pub claimer: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct GameSettled {
+    pub game_id: String,
+    pub players_paid: u8,
+    pub players_remaining: u8,
+}
+
+#[event]
+pub struct BadgesConfigured {
+    pub merkle_tree: Pubkey,
+    pub collection_mint: Pubkey,
+    pub uri_base: String,
+}
+
+#[event]
+pub struct AchievementMinted {
+    pub wallet: Pubkey,
+    pub achievement: Achievement,
+}
+
+#[event]
+pub struct ProfileCreated {
+    pub wallet: Pubkey,
+    pub rating: u32,
+}
+
+#[event]
+pub struct ProfileMigrated {
+    pub wallet: Pubkey,
+}
+
+#[event]
+pub struct GameResultRecorded {
+    pub game_id: String,
+    pub player: Pubkey,
+    pub games_finished: u32,
+}
+
+#[event]
+pub struct RatingsSettled {
+    pub game_id: String,
+    pub changes: Vec<RatingChange>,
+}
+
+#[event]
+pub struct GameSponsored {
+    pub game_id: String,
+    pub sponsor: Pubkey,
+    pub amount: u64,
+    pub sponsor_pool: u64,
+}
+
+#[event]
+pub struct GameCancelled {
+    pub game_id: String,
+    pub player_count: u8,
+    pub sponsor_pool: u64,
+}
+
+#[event]
+pub struct SponsorshipRefunded {
+    pub game_id: String,
+    pub sponsor: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct PredictionPoolCreated {
+    pub game_id: String,
+    pub pool: Pubkey,
+    pub close_day: u16,
+}
+
+#[event]
+pub struct BetPlaced {
+    pub game_id: String,
+    pub bettor: Pubkey,
+    pub side: PredictionSide,
+    pub amount: u64,
+    pub town_total: u64,
+    pub mafia_total: u64,
+}
+
+#[event]
+pub struct PredictionClaimed {
+    pub game_id: String,
+    pub bettor: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct TournamentCreated {
+    pub tournament: Pubkey,
+    pub tournament_id: u32,
+    pub buy_in: u64,
+    pub players_per_game: u8,
+    pub registration_end: i64,
+}
+
+#[event]
+pub struct TournamentRegistered {
+    pub tournament: Pubkey,
+    pub player: Pubkey,
+    pub entrant_count: u8,
+}
+
+#[event]
+pub struct TournamentGameCreated {
+    pub tournament: Pubkey,
+    pub round: u8,
+    pub game: Pubkey,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
-pub struct Player {
-    pub address: Pubkey,
-    pub is_alive: bool,
-    pub role: Role,
-    pub vote_target: Option<Pubkey>,
-    pub joined_at: i64,
+#[event]
+pub struct TournamentRoundAdvanced {
+    pub tournament: Pubkey,
+    pub round: u8,
+    pub survivors: u8,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct Vote {
-    pub voter: Pubkey,
-    pub target: Pubkey,
-    pub timestamp: i64,
+#[event]
+pub struct TournamentFinished {
+    pub tournament: Pubkey,
+    pub champions: Vec<Pubkey>,
 }
 
-// Enums
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
-pub enum GameState {
-    WaitingForPlayers,
-    Active,
-    Finished,
+#[event]
+pub struct TournamentCancelled {
+    pub tournament: Pubkey,
+    pub entrant_count: u8,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
-pub enum GamePhase {
-    Lobby,
-    Day,
-    Night,
+#[event]
+pub struct TournamentEntryRefunded {
+    pub tournament: Pubkey,
+    pub player: Pubkey,
+    pub amount: u64,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
-pub enum Role {
-    Unknown,
-    Mafia,
-    Detective,
-    Doctor,
-    Civilian,
+#[event]
+pub struct TournamentPrizeClaimed {
+    pub tournament: Pubkey,
+    pub player: Pubkey,
+    pub amount: u64,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq)]
-pub enum Winner {
-    Mafia,
-    Town,
+#[event]
+pub struct SeasonCreated {
+    pub season: Pubkey,
+    pub season_id: u32,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub prize_share_bps: u16,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub enum NightActionType {
-    MafiaKill,
-    DetectiveInvestigate,
-    DoctorHeal,
+#[event]
+pub struct GameEnteredSeason {
+    pub game_id: String,
+    pub season: Pubkey,
 }
 
-// Events
 #[event]
-pub struct GameCreated {
-    pub game_id: String,
-    pub name: String,
-    pub creator: Pubkey,
-    pub max_players: u8,
-    pub entry_fee: u64,
-    pub is_public: bool,
+pub struct SeasonFrozen {
+    pub season: Pubkey,
+    pub prize_pool: u64,
+    pub standings: Vec<LeaderboardEntry>,
 }
 
 #[event]
-pub struct PlayerJoined {
-    pub game_id: String,
-    pub player: Pubkey,
-    pub player_count: u8,
+pub struct SeasonPrizeClaimed {
+    pub season: Pubkey,
+    pub claimer: Pubkey,
+    pub rank: u8,
+    pub amount: u64,
 }
 
 #[event]
-pub struct GameStarted {
-    pub game_id: String,
-    pub player_count: u8,
-    pub day_count: u16,
+pub struct ProtocolConfigUpdated {
+    pub admin: Pubkey,
+    pub treasury: Pubkey,
+    pub rake_bps: u16,
+    pub claim_window_secs: u32,
+    pub sweep_to_season: bool,
+    pub referral_share_bps: u16,
 }
 
 #[event]
-pub struct VoteCast {
+pub struct UnclaimedSwept {
     pub game_id: String,
-    pub voter: Pubkey,
-    pub target: Pubkey,
-    pub total_votes: u8,
+    /// Treasury or season account that received the funds
+    pub destination: Pubkey,
+    pub amount: u64,
 }
 
 #[event]
-pub struct PhaseChanged {
+pub struct FeesCollected {
     pub game_id: String,
-    pub new_phase: GamePhase,
-    pub day_count: u16,
+    pub treasury: Pubkey,
+    pub amount: u64,
+    /// Part of the rake credited to referrers instead
+    pub referrals: u64,
 }
 
 #[event]
-pub struct PlayerEliminated {
-    pub game_id: String,
-    pub player: Pubkey,
-    pub phase: GamePhase,
-    pub day_count: u16,
+pub struct ReferralAccountCreated {
+    pub referrer: Pubkey,
 }
 
 #[event]
-pub struct GameEnded {
-    pub game_id: String,
-    pub winner: Winner,
-    pub day_count: u16,
+pub struct PlayerReferred {
+    pub player: Pubkey,
+    pub referrer: Pubkey,
 }
 
 #[event]
-pub struct NightActionPerformed {
+pub struct ReferralCredited {
     pub game_id: String,
-    pub actor: Pubkey,
-    pub action_type: NightActionType,
-    pub target: Option<Pubkey>,
+    pub referrer: Pubkey,
+    pub amount: u64,
 }
 
 #[event]
-pub struct WinningsClaimed {
-    pub game_id: String,
-    pub claimer: Pubkey,
+pub struct ReferralClaimed {
+    pub referrer: Pubkey,
     pub amount: u64,
 }
 
@@ -721,4 +4634,737 @@ pub enum ErrorCode {
     NotWinner,
     #[msg("Too many players")]
     TooManyPlayers,
+    #[msg("Invalid role deck")]
+    InvalidRoleDeck,
+    #[msg("Invalid game settings")]
+    InvalidSettings,
+    #[msg("No shots remaining")]
+    NoShotsRemaining,
+    #[msg("Role already revealed")]
+    AlreadyRevealed,
+    #[msg("Seance already open")]
+    SeanceAlreadyOpen,
+    #[msg("Not in a seance")]
+    NotInSeance,
+    #[msg("Seance message log is full")]
+    SeanceFull,
+    #[msg("Potion already used")]
+    PotionUsed,
+    #[msg("Mafia target not settled yet")]
+    MafiaTargetNotSettled,
+    #[msg("Mafia target already settled")]
+    MafiaTargetLocked,
+    #[msg("Will is too long")]
+    WillTooLong,
+    #[msg("Phase timer has not run out")]
+    PhaseNotOver,
+    #[msg("Game does not meet the rules of its mode")]
+    InvalidGameMode,
+    #[msg("Game does not meet ranked requirements")]
+    RankedRequirementsNotMet,
+    #[msg("Game is not ranked")]
+    NotRanked,
+    #[msg("Ratings already settled")]
+    RatingsAlreadySettled,
+    #[msg("Invalid player profile accounts")]
+    InvalidProfileAccounts,
+    #[msg("Game result already recorded")]
+    ResultAlreadyRecorded,
+    #[msg("Invalid season")]
+    InvalidSeason,
+    #[msg("Season is not active")]
+    SeasonNotActive,
+    #[msg("Season has not ended")]
+    SeasonNotOver,
+    #[msg("Season already frozen")]
+    SeasonFrozen,
+    #[msg("Prize already claimed")]
+    PrizeAlreadyClaimed,
+    #[msg("Not the protocol admin")]
+    NotAdmin,
+    #[msg("Invalid rake")]
+    InvalidRake,
+    #[msg("Invalid treasury")]
+    InvalidTreasury,
+    #[msg("Fees already collected")]
+    FeesAlreadyCollected,
+    #[msg("Token accounts required for this game")]
+    MissingTokenAccounts,
+    #[msg("Invalid mint")]
+    InvalidMint,
+    #[msg("Invalid escrow account")]
+    InvalidEscrow,
+    #[msg("Winnings already paid")]
+    AlreadyPaid,
+    #[msg("Payout accounts do not match the unpaid winners")]
+    InvalidPayoutAccounts,
+    #[msg("Invalid claim window")]
+    InvalidClaimWindow,
+    #[msg("Claim window has closed")]
+    ClaimWindowClosed,
+    #[msg("Claim window is still open")]
+    ClaimWindowOpen,
+    #[msg("Invalid tournament")]
+    InvalidTournament,
+    #[msg("Tournament registration is closed")]
+    RegistrationClosed,
+    #[msg("Tournament registration is still open")]
+    RegistrationOpen,
+    #[msg("Tournament is full")]
+    TournamentFull,
+    #[msg("Tournament is not in progress")]
+    TournamentNotInProgress,
+    #[msg("Tournament is not finished")]
+    TournamentNotFinished,
+    #[msg("All games for this round already exist")]
+    RoundComplete,
+    #[msg("Round is not finished")]
+    RoundNotFinished,
+    #[msg("Accounts do not match the round's games")]
+    InvalidTournamentGames,
+    #[msg("Betting is closed")]
+    BettingClosed,
+    #[msg("Players cannot bet on their own game")]
+    BettorCannotPlay,
+    #[msg("Invalid bet")]
+    InvalidBet,
+    #[msg("Invalid sponsorship")]
+    InvalidSponsorship,
+    #[msg("Game is not cancelled")]
+    GameNotCancelled,
+    #[msg("Invalid referral")]
+    InvalidReferral,
+    #[msg("Players cannot refer themselves")]
+    SelfReferral,
+    #[msg("No referral rewards to claim")]
+    NoReferralRewards,
+    #[msg("Invalid badge configuration")]
+    InvalidBadgeConfig,
+    #[msg("Achievement not earned")]
+    AchievementNotEarned,
+    #[msg("Tournament is not in registration")]
+    TournamentNotInRegistration,
+    #[msg("Tournament has enough entrants to start")]
+    TournamentCanStart,
+    #[msg("Tournament is not cancelled")]
+    TournamentNotCancelled,
+    #[msg("Only the season authority can do this")]
+    NotSeasonAuthority,
+    #[msg("The Lovers have not been paired yet")]
+    LoversNotPaired,
+    #[msg("The Lovers are already paired")]
+    LoversAlreadyPaired,
+    #[msg("The slot that picks the Lovers has not landed yet")]
+    LoversSeedPending,
+    #[msg("Invalid SlotHashes sysvar data")]
+    InvalidSlotHashes,
+    #[msg("No more seances can be held this game")]
+    SeanceLimitReached,
+    #[msg("Not allowed once the creator has overridden the game")]
+    CreatorOverride,
+    #[msg("The game's creator can't bet on it")]
+    CreatorCannotBet,
+    #[msg("Not allowed while outsiders have money on the game")]
+    OutsideStakes,
+    #[msg("Profile already has the current layout")]
+    ProfileUpToDate,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_stubs::{self, SyscallStubs};
+    use anchor_lang::Discriminator;
+    use std::cell::RefCell;
+    use std::sync::Once;
+    
+    thread_local! {
+        static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+    
+    /// Keeps emitted events so tests can check what was announced
+    struct EventLog;
+    
+    impl SyscallStubs for EventLog {
+        fn sol_log_data(&self, data: &[&[u8]]) {
+            EVENTS.with(|events| events.borrow_mut().push(data.concat()));
+        }
+    }
+    
+    fn capture_events() {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(EventLog));
+        });
+        EVENTS.with(|events| events.borrow_mut().clear());
+    }
+    
+    fn emitted<T: Discriminator + AnchorDeserialize>() -> Vec<T> {
+        EVENTS.with(|events| {
+            events.borrow().iter()
+                .filter(|data| data.starts_with(&T::DISCRIMINATOR))
+                .map(|data| T::try_from_slice(&data[8..]).unwrap())
+                .collect()
+        })
+    }
+    
+    /// An active game at night with one player per role, in order
+    fn night_game(roles: &[Role]) -> Game {
+        Game {
+            game_id: "test".to_string(),
+            name: "test".to_string(),
+            creator: Pubkey::new_unique(),
+            max_players: 20,
+            entry_fee: 0,
+            is_public: true,
+            state: GameState::Active,
+            current_phase: GamePhase::Night,
+            day_count: 1,
+            created_at: 0,
+            phase_start_time: 0,
+            phase_end_time: 0,
+            players: roles.iter()
+                .map(|role| Player { role: role.clone(), ..Player::new(Pubkey::new_unique(), 0) })
+                .collect(),
+            roles: roles.to_vec(),
+            votes: Vec::new(),
+            eliminated_players: Vec::new(),
+            winner: None,
+            settings: GameSettings::default(),
+            night_actions: Vec::new(),
+            seances: Vec::new(),
+            mafia_target: None,
+            on_trial: None,
+            mode: GameMode::Classic,
+            ratings_settled: false,
+            season: None,
+            season_share_bps: 0,
+            rake_bps: 0,
+            claim_window_secs: 0,
+            referral_share_bps: 0,
+            claim_deadline: 0,
+            fees_collected: false,
+            pot: 0,
+            sponsor_pool: 0,
+            mint: None,
+            escrow_bump: 0,
+            lovers_seed_slot: None,
+            creator_override: false,
+            has_prediction_pool: false,
+        }
+    }
+    
+    fn act(game: &mut Game, actor: usize, action_type: NightActionType, target: usize) {
+        let record = NightActionRecord {
+            actor: game.players[actor].address,
+            action_type,
+            target: game.players[target].address,
+        };
+        game.night_actions.push(record);
+    }
+    
+    fn alive(game: &Game) -> Vec<bool> {
+        game.players.iter().map(|p| p.is_alive).collect()
+    }
+    
+    #[test]
+    fn heal_saves_the_mafia_target() {
+        let mut game = night_game(&[Role::Mafia, Role::Doctor, Role::Civilian]);
+        act(&mut game, 0, NightActionType::MafiaKill, 2);
+        act(&mut game, 1, NightActionType::DoctorHeal, 2);
+        
+        resolve_night(&mut game).unwrap();
+        
+        assert_eq!(alive(&game), [true, true, true]);
+        assert_eq!(game.players[1].stats.successful_heals, 1);
+    }
+    
+    #[test]
+    fn roleblock_lands_before_the_heal() {
+        let mut game = night_game(&[Role::Mafia, Role::Escort, Role::Doctor, Role::Civilian]);
+        act(&mut game, 0, NightActionType::MafiaKill, 3);
+        act(&mut game, 1, NightActionType::Roleblock, 2);
+        act(&mut game, 2, NightActionType::DoctorHeal, 3);
+        
+        resolve_night(&mut game).unwrap();
+        
+        assert_eq!(alive(&game), [true, true, true, false]);
+        assert_eq!(game.players[2].stats.successful_heals, 0);
+    }
+    
+    #[test]
+    fn bodyguard_dies_in_place_and_takes_the_attacker() {
+        let mut game = night_game(&[Role::Mafia, Role::Bodyguard, Role::Civilian]);
+        act(&mut game, 0, NightActionType::MafiaKill, 2);
+        act(&mut game, 1, NightActionType::Guard, 2);
+        
+        resolve_night(&mut game).unwrap();
+        
+        assert_eq!(alive(&game), [false, false, true]);
+    }
+    
+    #[test]
+    fn heal_spares_the_bodyguard() {
+        let mut game = night_game(&[Role::Mafia, Role::Bodyguard, Role::Doctor, Role::Civilian]);
+        act(&mut game, 0, NightActionType::MafiaKill, 3);
+        act(&mut game, 1, NightActionType::Guard, 3);
+        act(&mut game, 2, NightActionType::DoctorHeal, 3);
+        
+        resolve_night(&mut game).unwrap();
+        
+        assert_eq!(alive(&game), [true, true, true, true]);
+    }
+    
+    #[test]
+    fn settled_target_stands_when_its_picker_is_blocked() {
+        let mut game = night_game(&[Role::Mafia, Role::Mafia, Role::Escort, Role::Civilian, Role::Civilian]);
+        act(&mut game, 0, NightActionType::MafiaKill, 3);
+        act(&mut game, 1, NightActionType::MafiaKill, 4);
+        act(&mut game, 2, NightActionType::Roleblock, 0);
+        game.mafia_target = Some(game.players[3].address);
+        
+        resolve_night(&mut game).unwrap();
+        
+        assert_eq!(alive(&game), [true, true, true, false, true]);
+        assert_eq!(game.mafia_target, None);
+    }
+    
+    #[test]
+    fn fully_blocked_mafia_kills_nobody() {
+        let mut game = night_game(&[Role::Mafia, Role::Escort, Role::Civilian]);
+        act(&mut game, 0, NightActionType::MafiaKill, 2);
+        act(&mut game, 1, NightActionType::Roleblock, 0);
+        game.mafia_target = Some(game.players[2].address);
+        
+        resolve_night(&mut game).unwrap();
+        
+        assert_eq!(alive(&game), [true, true, true]);
+    }
+    
+    #[test]
+    fn investigations_resolve_after_kills() {
+        capture_events();
+        let mut game = night_game(&[Role::Mafia, Role::Detective, Role::Detective, Role::Escort, Role::Detective]);
+        act(&mut game, 0, NightActionType::MafiaKill, 1);
+        act(&mut game, 1, NightActionType::DetectiveInvestigate, 0);
+        act(&mut game, 2, NightActionType::DetectiveInvestigate, 0);
+        act(&mut game, 3, NightActionType::Roleblock, 4);
+        act(&mut game, 4, NightActionType::DetectiveInvestigate, 0);
+        
+        resolve_night(&mut game).unwrap();
+        
+        // Only the Detective who survived the night and wasn't blocked learns anything
+        let investigations = emitted::<PlayerInvestigated>();
+        assert_eq!(investigations.len(), 1);
+        assert_eq!(investigations[0].detective, game.players[2].address);
+        assert_eq!(investigations[0].target, game.players[0].address);
+        assert!(investigations[0].result == InvestigationResult::Mafia);
+    }
+    
+    #[test]
+    fn even_match_moves_rating_by_half_k() {
+        assert_eq!(updated_rating(1200, 1200, true), 1216);
+        assert_eq!(updated_rating(1200, 1200, false), 1184);
+    }
+    
+    #[test]
+    fn upsets_move_ratings_further() {
+        // 400 points apart, the favourite is expected to win 90.91% of the time
+        assert_eq!(updated_rating(1200, 1600, true), 1229);
+        assert_eq!(updated_rating(1200, 1600, false), 1198);
+        assert_eq!(updated_rating(1600, 1200, true), 1602);
+        assert_eq!(updated_rating(1600, 1200, false), 1571);
+    }
+    
+    #[test]
+    fn rating_gaps_interpolate_and_clamp() {
+        // Halfway between the 0 and 50 point entries
+        assert_eq!(updated_rating(1200, 1225, true), 1217);
+        // Anything past 800 points reads as 800
+        assert_eq!(updated_rating(1000, 3000, true), updated_rating(1000, 1800, true));
+        assert_eq!(updated_rating(1000, 3000, true), 1031);
+    }
+    
+    #[test]
+    fn rating_never_drops_below_zero() {
+        assert_eq!(updated_rating(10, 10, false), 0);
+    }
+    
+    fn season_with(ratings: &[u32], frozen_pool: u64) -> Season {
+        let mut season = Season {
+            season_id: 1,
+            authority: Pubkey::new_unique(),
+            start_time: 0,
+            end_time: 0,
+            prize_share_bps: 0,
+            prize_pool: frozen_pool,
+            frozen_pool,
+            frozen: true,
+            leaderboard: Vec::new(),
+        };
+        for rating in ratings {
+            season.record_rating(Pubkey::new_unique(), *rating);
+        }
+        season
+    }
+    
+    #[test]
+    fn leaderboard_keeps_best_ratings_first() {
+        let mut season = season_with(&[1200, 1400, 1300], 0);
+        let ratings: Vec<u32> = season.leaderboard.iter().map(|e| e.rating).collect();
+        assert_eq!(ratings, [1400, 1300, 1200]);
+        
+        // A player's new rating replaces their old entry
+        let player = season.leaderboard[2].player;
+        season.record_rating(player, 1500);
+        assert_eq!(season.leaderboard.len(), 3);
+        assert_eq!(season.leaderboard[0].player, player);
+    }
+    
+    #[test]
+    fn season_prizes_are_weighted_by_rank() {
+        let season = season_with(&[1500, 1400, 1300, 1200], 1_000);
+        let prizes: Vec<u64> = (0..4).map(|rank| season.prize_for_rank(rank)).collect();
+        assert_eq!(prizes, [400, 300, 200, 100]);
+        
+        // Rounding leaves dust in the season rather than overpaying
+        let season = season_with(&[1500, 1400, 1300], 100);
+        let prizes: Vec<u64> = (0..3).map(|rank| season.prize_for_rank(rank)).collect();
+        assert_eq!(prizes, [50, 33, 16]);
+    }
+    
+    #[test]
+    fn season_prizes_do_not_overflow_large_pools() {
+        let season = season_with(&[1500, 1400, 1300, 1200], u64::MAX);
+        // u64::MAX * 4 / 10, worked out without the intermediate product
+        assert_eq!(season.prize_for_rank(0), 7_378_697_629_483_820_646);
+    }
+    
+    /// Two Mafia and three Town, the last of whom died, with a 1,000 lamport pot
+    fn payout_game(rule: PayoutRule) -> Game {
+        let mut game = night_game(&[Role::Mafia, Role::Mafia, Role::Civilian, Role::Doctor, Role::Civilian]);
+        game.players[4].is_alive = false;
+        game.settings.payout_rule = rule;
+        game.pot = 1_000;
+        game
+    }
+    
+    #[test]
+    fn whole_faction_splits_evenly_with_dust() {
+        let game = payout_game(PayoutRule::WholeFaction);
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, 333, 333, 333]);
+        assert_eq!(payout_dust(&game, &Winner::Town), 1);
+    }
+    
+    #[test]
+    fn survivors_only_leaves_out_the_dead() {
+        let game = payout_game(PayoutRule::SurvivorsOnly);
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, 500, 500, 0]);
+        assert_eq!(payout_dust(&game, &Winner::Town), 0);
+    }
+    
+    #[test]
+    fn survivor_bonus_weights_the_living() {
+        let game = payout_game(PayoutRule::SurvivorBonus { survivor_weight: 3 });
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, 428, 428, 142]);
+        assert_eq!(payout_dust(&game, &Winner::Town), 2);
+    }
+    
+    #[test]
+    fn mvp_bonus_goes_to_the_winner_with_most_correct_votes() {
+        let mut game = payout_game(PayoutRule::MvpBonus { bonus_bps: 1_000 });
+        game.players[0].stats.correct_votes = 5;
+        game.players[2].stats.correct_votes = 1;
+        game.players[3].stats.correct_votes = 2;
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, 300, 400, 300]);
+        
+        // Ties go to whoever joined first
+        game.players[2].stats.correct_votes = 2;
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, 400, 300, 300]);
+    }
+    
+    #[test]
+    fn mvp_bonus_stays_in_the_pool_without_an_mvp() {
+        let game = payout_game(PayoutRule::MvpBonus { bonus_bps: 1_000 });
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, 333, 333, 333]);
+    }
+    
+    #[test]
+    fn cuts_come_off_the_pot_before_sponsor_bonuses() {
+        let mut game = payout_game(PayoutRule::SurvivorsOnly);
+        game.rake_bps = 500;
+        game.season = Some(Pubkey::new_unique());
+        game.season_share_bps = 1_000;
+        game.sponsor_pool = 150;
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, 500, 500, 0]);
+    }
+    
+    #[test]
+    fn payouts_do_not_overflow_large_pots() {
+        let mut game = payout_game(PayoutRule::WholeFaction);
+        game.pot = u64::MAX;
+        game.rake_bps = 1;
+        let prize = u64::MAX - u64::MAX / 10_000;
+        assert_eq!(prize_pool(&game), prize);
+        assert_eq!(payouts(&game, &Winner::Town), [0, 0, prize / 3, prize / 3, prize / 3]);
+    }
+    
+    /// A finished tournament whose entrants went out in the given rounds
+    fn tournament_with(round: u8, eliminated: &[Option<u8>], prize_split_bps: Vec<u16>) -> Tournament {
+        Tournament {
+            tournament_id: 1,
+            authority: Pubkey::new_unique(),
+            buy_in: 0,
+            players_per_game: 8,
+            registration_end: 0,
+            state: TournamentState::Finished,
+            round,
+            pool: 1_000,
+            prize_split_bps,
+            entrants: eliminated.iter()
+                .map(|eliminated_round| TournamentEntrant {
+                    player: Pubkey::new_unique(),
+                    eliminated_round: *eliminated_round,
+                    paid_out: false,
+                })
+                .collect(),
+            games: Vec::new(),
+            round_deadline: 0,
+        }
+    }
+    
+    #[test]
+    fn placements_split_their_share() {
+        let tournament = tournament_with(
+            2,
+            &[None, None, Some(2), Some(2), Some(2), Some(1), Some(1), Some(1), Some(1)],
+            vec![6_000, 3_000, 1_000],
+        );
+        let prizes: Vec<u64> = (0..9).map(|index| tournament.prize_for(index)).collect();
+        assert_eq!(prizes, [300, 300, 100, 100, 100, 25, 25, 25, 25]);
+    }
+    
+    #[test]
+    fn unreached_placements_go_to_the_champions() {
+        let tournament = tournament_with(
+            1,
+            &[None, None, Some(1), Some(1), Some(1), Some(1), Some(1)],
+            vec![6_000, 3_000, 1_000],
+        );
+        let prizes: Vec<u64> = (0..7).map(|index| tournament.prize_for(index)).collect();
+        assert_eq!(prizes, [350, 350, 60, 60, 60, 60, 60]);
+    }
+    
+    #[test]
+    fn placements_past_the_split_win_nothing() {
+        let tournament = tournament_with(2, &[None, Some(2), Some(1)], vec![10_000]);
+        let prizes: Vec<u64> = (0..3).map(|index| tournament.prize_for(index)).collect();
+        assert_eq!(prizes, [1_000, 0, 0]);
+    }
+    
+    fn bet(side: PredictionSide, amount: u64) -> PredictionBet {
+        PredictionBet {
+            bettor: Pubkey::new_unique(),
+            side,
+            amount,
+            claimed: false,
+        }
+    }
+    
+    fn prediction_pool(town_total: u64, mafia_total: u64) -> PredictionPool {
+        PredictionPool {
+            game: Pubkey::new_unique(),
+            close_day: 0,
+            town_total,
+            mafia_total,
+        }
+    }
+    
+    #[test]
+    fn winning_bets_split_the_whole_pool() {
+        let pool = prediction_pool(300, 700);
+        assert_eq!(pool.payout(Some(&Winner::Town), &bet(PredictionSide::Town, 100)), 333);
+        assert_eq!(pool.payout(Some(&Winner::Town), &bet(PredictionSide::Mafia, 700)), 0);
+        assert_eq!(pool.payout(Some(&Winner::Mafia), &bet(PredictionSide::Mafia, 350)), 500);
+    }
+    
+    #[test]
+    fn stakes_are_refunded_when_nobody_backed_the_winner() {
+        let pool = prediction_pool(0, 700);
+        assert_eq!(pool.payout(Some(&Winner::Town), &bet(PredictionSide::Mafia, 700)), 700);
+        
+        // A Lovers win or no winner at all refunds both sides
+        let pool = prediction_pool(300, 700);
+        assert_eq!(pool.payout(Some(&Winner::Lovers), &bet(PredictionSide::Town, 300)), 300);
+        assert_eq!(pool.payout(None, &bet(PredictionSide::Mafia, 700)), 700);
+    }
+    
+    #[test]
+    fn prediction_payouts_do_not_overflow_large_pools() {
+        let pool = prediction_pool(u64::MAX / 2, u64::MAX / 2);
+        assert_eq!(pool.payout(Some(&Winner::Town), &bet(PredictionSide::Town, u64::MAX / 2)), u64::MAX - 1);
+    }
+    
+    /// Raw SlotHashes data for the given slots, newest first, each hashed to its own slot number
+    fn slot_hashes_data(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+    
+    #[test]
+    fn slot_hash_skips_to_the_next_landed_slot() {
+        let data = slot_hashes_data(&[105, 104, 102, 101]);
+        assert_eq!(slot_hash_at(&data, 104).unwrap(), Some([104; 32]));
+        // Slot 103 was skipped, so the next one stands in for it
+        assert_eq!(slot_hash_at(&data, 103).unwrap(), Some([104; 32]));
+        assert_eq!(slot_hash_at(&data, 101).unwrap(), Some([101; 32]));
+    }
+    
+    #[test]
+    fn slot_hash_is_pending_until_the_slot_lands() {
+        let data = slot_hashes_data(&[105, 104]);
+        assert!(slot_hash_at(&data, 106).is_err());
+    }
+    
+    #[test]
+    fn slot_hash_ages_out() {
+        let data = slot_hashes_data(&[105, 104]);
+        assert_eq!(slot_hash_at(&data, 103).unwrap(), None);
+    }
+    
+    #[test]
+    fn lovers_are_always_two_different_players() {
+        for seed in 0..200 {
+            for player_count in [4, 5, 9, 20] {
+                let (first, second) = lovers_from_seed(seed, player_count);
+                assert!(first < player_count && second < player_count);
+                assert_ne!(first, second);
+            }
+        }
+    }
+    
+    #[test]
+    fn witch_can_use_both_potions_in_one_night() {
+        let mut game = night_game(&[Role::Mafia, Role::Witch, Role::Civilian, Role::Civilian]);
+        act(&mut game, 0, NightActionType::MafiaKill, 2);
+        game.mafia_target = Some(game.players[2].address);
+        act(&mut game, 1, NightActionType::WitchHeal, 2);
+        act(&mut game, 1, NightActionType::WitchPoison, 0);
+        
+        resolve_night(&mut game).unwrap();
+        
+        assert_eq!(alive(&game), [false, true, true, true]);
+        assert!(game.players[1].role_state.heal_potion_used);
+        assert!(game.players[1].role_state.poison_potion_used);
+    }
+    
+    #[test]
+    fn first_champion_takes_the_rounding_dust() {
+        let tournament = tournament_with(2, &[None, None, None, Some(2), Some(1)], vec![10_000]);
+        let prizes: Vec<u64> = (0..5).map(|index| tournament.prize_for(index)).collect();
+        assert_eq!(prizes, [334, 333, 333, 0, 0]);
+    }
+    
+    fn round_with(game: &Game) -> Tournament {
+        let mut tournament = tournament_with(1, &vec![None; game.players.len()], vec![10_000]);
+        for (entrant, player) in tournament.entrants.iter_mut().zip(game.players.iter()) {
+            entrant.player = player.address;
+        }
+        tournament.round_deadline = 100;
+        tournament
+    }
+    
+    #[test]
+    fn finished_round_game_knocks_out_the_losers() {
+        let mut game = night_game(&[Role::Mafia, Role::Civilian, Role::Civilian]);
+        game.state = GameState::Finished;
+        game.winner = Some(Winner::Town);
+        let mut tournament = round_with(&game);
+        
+        tournament.apply_round_game(&game, 0).unwrap();
+        let eliminated: Vec<Option<u8>> = tournament.entrants.iter().map(|e| e.eliminated_round).collect();
+        assert_eq!(eliminated, [Some(1), None, None]);
+    }
+    
+    #[test]
+    fn stalled_round_game_is_a_draw_after_the_deadline() {
+        let game = night_game(&[Role::Mafia, Role::Civilian, Role::Civilian]);
+        let mut tournament = round_with(&game);
+        
+        assert_eq!(tournament.apply_round_game(&game, 99).unwrap_err(), ErrorCode::RoundNotFinished.into());
+        tournament.apply_round_game(&game, 100).unwrap();
+        assert!(tournament.entrants.iter().all(|e| e.eliminated_round.is_none()));
+    }
+    
+    #[test]
+    fn prediction_pool_blocks_creator_overrides() {
+        let mut game = night_game(&[Role::Mafia, Role::Civilian, Role::Civilian]);
+        ensure_overridable(&game).unwrap();
+        
+        game.has_prediction_pool = true;
+        assert_eq!(ensure_overridable(&game).unwrap_err(), ErrorCode::OutsideStakes.into());
+    }
+    
+    #[test]
+    fn sponsorships_block_creator_overrides() {
+        let mut game = night_game(&[Role::Mafia, Role::Civilian, Role::Civilian]);
+        game.sponsor_pool = 500;
+        assert_eq!(ensure_overridable(&game).unwrap_err(), ErrorCode::OutsideStakes.into());
+        
+        // Once every sponsor is refunded, nothing is at stake again
+        game.sponsor_pool = 0;
+        ensure_overridable(&game).unwrap();
+    }
+    
+    fn profile_with(wins_as_mafia: u32, doctor_survivals: u32, mafia_lynches: u32) -> PlayerProfile {
+        PlayerProfile {
+            wallet: Pubkey::new_unique(),
+            rating: PlayerProfile::STARTING_RATING,
+            ranked_games: 0,
+            wins_as_town: 0,
+            wins_as_mafia,
+            games_joined: 0,
+            games_finished: 0,
+            times_lynched: 0,
+            correct_mafia_votes: 0,
+            successful_heals: 0,
+            sol_won: 0,
+            referrer: None,
+            doctor_survivals,
+            mafia_lynches,
+        }
+    }
+    
+    #[test]
+    fn achievements_need_their_milestone() {
+        let fresh = profile_with(0, 0, 9);
+        assert!(!Achievement::FirstMafiaWin.is_earned(&fresh));
+        assert!(!Achievement::SurvivedAsDoctor.is_earned(&fresh));
+        assert!(!Achievement::TenCorrectLynches.is_earned(&fresh));
+        
+        let veteran = profile_with(1, 1, 10);
+        assert!(Achievement::FirstMafiaWin.is_earned(&veteran));
+        assert!(Achievement::SurvivedAsDoctor.is_earned(&veteran));
+        assert!(Achievement::TenCorrectLynches.is_earned(&veteran));
+    }
+    
+    #[test]
+    fn migrated_profile_reads_appended_fields_as_zero() {
+        let mut profile = profile_with(3, 0, 0);
+        profile.referrer = Some(Pubkey::new_unique());
+        let mut data = Vec::new();
+        profile.try_serialize(&mut data).unwrap();
+        
+        // A profile from before the appended fields, zero-filled to the current size
+        data.truncate(data.len() - 8);
+        data.resize(PlayerProfile::SPACE, 0);
+        let migrated = PlayerProfile::try_deserialize(&mut data.as_slice()).unwrap();
+        assert_eq!(migrated.wins_as_mafia, 3);
+        assert_eq!(migrated.referrer, profile.referrer);
+        assert_eq!((migrated.doctor_survivals, migrated.mafia_lynches), (0, 0));
+    }
 }
\ No newline at end of file
@@ -78,9 +78,17 @@ async function main() {
 
     await program.methods
       .claimWinnings()
-      .accounts({
+      .accountsPartial({
         game: gamePDA,
         claimer: claimerKeypair.publicKey,
+        // Native SOL payout, so none of the token accounts are needed
+        tokenPot: {
+          mint: null,
+          escrow: null,
+          vault: null,
+          tokenAccount: null,
+          tokenProgram: null,
+        },
       })
       .signers([claimerKeypair])
       .rpc();
@@ -11,28 +11,52 @@ async function main() {
   const creator = provider.wallet as anchor.Wallet;
 
   // Game parameters
-  const gameId = "mafia-game-" + Date.now(); // Unique game ID
+  const gameName = "mafia-game-" + Date.now();
   const maxPlayers = 6;
   const entryFee = new anchor.BN(LAMPORTS_PER_SOL / 10); // 0.1 SOL
 
+  // Games are numbered by the counter, and fees follow the protocol config
+  const [counterPDA] = await PublicKey.findProgramAddress(
+    [Buffer.from("game_counter")],
+    program.programId
+  );
+  const [configPDA] = await PublicKey.findProgramAddress(
+    [Buffer.from("protocol_config")],
+    program.programId
+  );
+  const counter = await program.account.gameCounter.fetch(counterPDA);
+  const gameId = `game-${counter.count.toNumber() + 1}`;
+
   // Find PDA for the game
   const [gamePDA] = await PublicKey.findProgramAddress(
     [Buffer.from("game"), Buffer.from(gameId)],
     program.programId
   );
+  const [escrowPDA] = await PublicKey.findProgramAddress(
+    [Buffer.from("escrow"), gamePDA.toBuffer()],
+    program.programId
+  );
 
   console.log("Initializing game...");
   console.log("Game ID:", gameId);
+  console.log("Name:", gameName);
   console.log("Max Players:", maxPlayers);
   console.log("Entry Fee:", entryFee.toNumber() / LAMPORTS_PER_SOL, "SOL");
   console.log("Game PDA:", gamePDA.toString());
 
   try {
     await program.methods
-      .initializeGame(gameId, maxPlayers, entryFee)
-      .accounts({
+      .initializeGame(gameName, maxPlayers, entryFee, true)
+      .accountsPartial({
+        counter: counterPDA,
         game: gamePDA,
+        config: configPDA,
+        // Native SOL entry fee, so no mint, vault or token program
+        mint: null,
+        escrow: escrowPDA,
+        vault: null,
         creator: creator.publicKey,
+        tokenProgram: null,
         systemProgram: SystemProgram.programId,
       })
       .rpc();
@@ -22,6 +22,14 @@ async function main() {
     [Buffer.from("game"), Buffer.from(gameId)],
     program.programId
   );
+  const [profilePDA] = await PublicKey.findProgramAddress(
+    [Buffer.from("profile"), playerKeypair.publicKey.toBuffer()],
+    program.programId
+  );
+  const [predictionBetPDA] = await PublicKey.findProgramAddress(
+    [Buffer.from("bet"), gamePDA.toBuffer(), playerKeypair.publicKey.toBuffer()],
+    program.programId
+  );
 
   console.log("Joining game...");
   console.log("Game ID:", gameId);
@@ -37,10 +45,21 @@ async function main() {
     }
 
     await program.methods
-      .joinGame()
-      .accounts({
+      .joinGame(null)
+      .accountsPartial({
         game: gamePDA,
+        profile: profilePDA,
         player: playerKeypair.publicKey,
+        // No referrer, and a native SOL entry fee
+        referral: null,
+        predictionBet: predictionBetPDA,
+        tokenPot: {
+          mint: null,
+          escrow: null,
+          vault: null,
+          tokenAccount: null,
+          tokenProgram: null,
+        },
         systemProgram: SystemProgram.programId,
       })
       .signers([playerKeypair])
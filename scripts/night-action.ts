@@ -11,14 +11,14 @@ async function main() {
   
   // Get parameters from command line
   const gameId = process.argv[2] || "mafia-game-1";
-  const actionType = process.argv[3]; // "mafiaKill", "detectiveInvestigate", "doctorHeal"
-  const targetPlayerPubkey = process.argv[4]; // Optional for some actions
+  const actionType = process.argv[3]; // "mafiaKill", "detectiveInvestigate", "doctorHeal", "vigilanteShoot", "roleblock", "guard", "witchHeal", "witchPoison"
+  const targetPlayerPubkey = process.argv[4];
   const actorKeypairArg = process.argv[5];
   
   if (!actionType) {
     console.error("❌ Please provide action type as second argument");
-    console.log("Usage: ts-node scripts/night-action.ts <gameId> <actionType> [targetPlayerPubkey] [actorKeypair]");
-    console.log("Action types: mafiaKill, detectiveInvestigate, doctorHeal");
+    console.log("Usage: ts-node scripts/night-action.ts <gameId> <actionType> <targetPlayerPubkey> [actorKeypair]");
+    console.log("Action types: mafiaKill, detectiveInvestigate, doctorHeal, vigilanteShoot, roleblock, guard, witchHeal, witchPoison");
     return;
   }
 
@@ -80,10 +80,60 @@ async function main() {
         target = new PublicKey(targetPlayerPubkey);
         break;
       case "detectiveInvestigate":
+        if (!targetPlayerPubkey) {
+          console.log("❌ Target player required for detective investigation!");
+          return;
+        }
         actionParams = { detectiveInvestigate: {} };
+        target = new PublicKey(targetPlayerPubkey);
         break;
       case "doctorHeal":
+        if (!targetPlayerPubkey) {
+          console.log("❌ Target player required for doctor heal!");
+          return;
+        }
         actionParams = { doctorHeal: {} };
+        target = new PublicKey(targetPlayerPubkey);
+        break;
+      case "vigilanteShoot":
+        if (!targetPlayerPubkey) {
+          console.log("❌ Target player required for vigilante shot!");
+          return;
+        }
+        actionParams = { vigilanteShoot: {} };
+        target = new PublicKey(targetPlayerPubkey);
+        break;
+      case "roleblock":
+        if (!targetPlayerPubkey) {
+          console.log("❌ Target player required for roleblock!");
+          return;
+        }
+        actionParams = { roleblock: {} };
+        target = new PublicKey(targetPlayerPubkey);
+        break;
+      case "guard":
+        if (!targetPlayerPubkey) {
+          console.log("❌ Target player required for bodyguard guard!");
+          return;
+        }
+        actionParams = { guard: {} };
+        target = new PublicKey(targetPlayerPubkey);
+        break;
+      case "witchHeal":
+        if (!gameAccount.mafiaTarget) {
+          console.log("❌ The Mafia has not settled on a target yet!");
+          return;
+        }
+        actionParams = { witchHeal: {} };
+        target = gameAccount.mafiaTarget;
+        break;
+      case "witchPoison":
+        if (!targetPlayerPubkey) {
+          console.log("❌ Target player required for witch poison!");
+          return;
+        }
+        actionParams = { witchPoison: {} };
+        target = new PublicKey(targetPlayerPubkey);
         break;
       default:
         console.log("❌ Invalid action type!");
@@ -99,7 +149,7 @@ async function main() {
       .signers([actorKeypair])
       .rpc();
 
-    console.log("✅ Night action performed successfully!");
+    console.log("✅ Night action recorded! It takes effect when the night is resolved.");
     
     // Fetch and display updated game state
     const updatedGameAccount = await program.account.game.fetch(gamePDA);
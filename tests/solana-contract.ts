@@ -16,11 +16,24 @@ describe("solana-contract", () => {
   const program = anchor.workspace.SolanaContract as Program<SolanaContract>;
   const creator = provider.wallet as anchor.Wallet;
 
-  const gameId = "mafia-game-1";
+  const gameName = "mafia-game-1";
   const maxPlayers = 4;
   const entryFee = new anchor.BN(LAMPORTS_PER_SOL / 10); // 0.1 SOL
+  const BPF_LOADER_UPGRADEABLE = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
 
+  let counterPDA: PublicKey;
+  let configPDA: PublicKey;
   let gamePDA: PublicKey;
+  let escrowPDA: PublicKey;
+
+  // These tests play for native SOL, so none of the token accounts are passed
+  const solPot = {
+    mint: null,
+    escrow: null,
+    vault: null,
+    tokenAccount: null,
+    tokenProgram: null,
+  };
 
   const players: { keypair: Keypair; role?: any; isAlive: boolean }[] = [];
   for (let i = 0; i < maxPlayers; i++) {
@@ -29,9 +42,35 @@ describe("solana-contract", () => {
 
   const nonPlayer = Keypair.generate();
 
+  // Accounts for joinGame without a referrer, which keeps a lifetime profile for each player
+  const joinAccounts = async (player: PublicKey) => {
+    const [profile] = await PublicKey.findProgramAddress(
+      [Buffer.from("profile"), player.toBuffer()],
+      program.programId
+    );
+    // Must not exist: anyone who bet on the game can't join it
+    const [predictionBet] = await PublicKey.findProgramAddress(
+      [Buffer.from("bet"), gamePDA.toBuffer(), player.toBuffer()],
+      program.programId
+    );
+    return {
+      game: gamePDA,
+      profile,
+      player,
+      referral: null,
+      predictionBet,
+      tokenPot: solPot,
+      systemProgram: SystemProgram.programId,
+    };
+  };
+
   before(async () => {
-    [gamePDA] = await PublicKey.findProgramAddress(
-      [Buffer.from("game"), Buffer.from(gameId)],
+    [counterPDA] = await PublicKey.findProgramAddress(
+      [Buffer.from("game_counter")],
+      program.programId
+    );
+    [configPDA] = await PublicKey.findProgramAddress(
+      [Buffer.from("protocol_config")],
       program.programId
     );
 
@@ -41,20 +80,66 @@ describe("solana-contract", () => {
       ...players.map(p => provider.connection.requestAirdrop(p.keypair.publicKey, 2 * LAMPORTS_PER_SOL))
     ];
     await Promise.all(airdropPromises);
+
+    // The counter and protocol config are singletons that an earlier run may have created
+    if (!(await provider.connection.getAccountInfo(counterPDA))) {
+      await program.methods
+        .initializeCounter()
+        .accountsPartial({
+          counter: counterPDA,
+          payer: creator.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+    if (!(await provider.connection.getAccountInfo(configPDA))) {
+      // Only the upgrade authority, which deployed the program, can set up the config
+      const [programData] = await PublicKey.findProgramAddress(
+        [program.programId.toBuffer()],
+        BPF_LOADER_UPGRADEABLE
+      );
+      await program.methods
+        .initializeProtocolConfig(creator.publicKey, 0, 7 * 24 * 60 * 60, false, 0)
+        .accountsPartial({
+          config: configPDA,
+          admin: creator.publicKey,
+          program: program.programId,
+          programData,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+    }
+
+    // Games are numbered by the counter, so the next one's address depends on it
+    const counter = await program.account.gameCounter.fetch(counterPDA);
+    [gamePDA] = await PublicKey.findProgramAddress(
+      [Buffer.from("game"), Buffer.from(`game-${counter.count.toNumber() + 1}`)],
+      program.programId
+    );
+    [escrowPDA] = await PublicKey.findProgramAddress(
+      [Buffer.from("escrow"), gamePDA.toBuffer()],
+      program.programId
+    );
   });
 
   it("Initializes a new game", async () => {
     await program.methods
-      .initializeGame(gameId, maxPlayers, entryFee)
-      .accounts({
+      .initializeGame(gameName, maxPlayers, entryFee, true)
+      .accountsPartial({
+        counter: counterPDA,
         game: gamePDA,
+        config: configPDA,
+        mint: null,
+        escrow: escrowPDA,
+        vault: null,
         creator: creator.publicKey,
+        tokenProgram: null,
         systemProgram: SystemProgram.programId,
       })
       .rpc();
 
     const gameAccount = await program.account.game.fetch(gamePDA);
-    assert.strictEqual(gameAccount.gameId, gameId, "Game ID should match");
+    assert.strictEqual(gameAccount.name, gameName, "Game name should match");
     assert.isTrue(gameAccount.creator.equals(creator.publicKey), "Creator should match");
     assert.strictEqual(gameAccount.maxPlayers, maxPlayers, "Max players should match");
     assert.isTrue(gameAccount.entryFee.eq(entryFee), "Entry fee should match");
@@ -62,19 +147,26 @@ describe("solana-contract", () => {
     assert.strictEqual(gameAccount.players.length, 0, "Game should have no players initially");
   });
 
-  it("Fails to initialize a game with the same ID", async () => {
+  it("Fails to initialize a game over an existing one", async () => {
     try {
       await program.methods
-        .initializeGame(gameId, maxPlayers, entryFee)
-        .accounts({
+        .initializeGame(gameName, maxPlayers, entryFee, true)
+        .accountsPartial({
+          counter: counterPDA,
           game: gamePDA,
+          config: configPDA,
+          mint: null,
+          escrow: escrowPDA,
+          vault: null,
           creator: creator.publicKey,
+          tokenProgram: null,
           systemProgram: SystemProgram.programId,
         })
         .rpc();
       assert.fail("Should have failed to initialize the same game twice.");
     } catch (err) {
-      assert.include(err.toString(), "Allocate: account Address { address: ", "Error should be due to account already in use");
+      // The counter has moved on, so the old address no longer matches the next game's seeds
+      assert.equal(err.error.errorCode.code, "ConstraintSeeds");
     }
   });
 
@@ -82,12 +174,8 @@ describe("solana-contract", () => {
     for (let i = 0; i < 2; i++) {
       const player = players[i];
       await program.methods
-        .joinGame()
-        .accounts({
-          game: gamePDA,
-          player: player.keypair.publicKey,
-          systemProgram: SystemProgram.programId,
-        })
+        .joinGame(null)
+        .accountsPartial(await joinAccounts(player.keypair.publicKey))
         .signers([player.keypair])
         .rpc();
     }
@@ -98,12 +186,8 @@ describe("solana-contract", () => {
   it("Fails when a player tries to join twice", async () => {
     try {
       await program.methods
-        .joinGame()
-        .accounts({
-          game: gamePDA,
-          player: players[0].keypair.publicKey,
-          systemProgram: SystemProgram.programId,
-        })
+        .joinGame(null)
+        .accountsPartial(await joinAccounts(players[0].keypair.publicKey))
         .signers([players[0].keypair])
         .rpc();
       assert.fail("A player should not be able to join twice.");
@@ -116,12 +200,8 @@ describe("solana-contract", () => {
     for (let i = 2; i < maxPlayers; i++) {
       const player = players[i];
       await program.methods
-        .joinGame()
-        .accounts({
-          game: gamePDA,
-          player: player.keypair.publicKey,
-          systemProgram: SystemProgram.programId,
-        })
+        .joinGame(null)
+        .accountsPartial(await joinAccounts(player.keypair.publicKey))
         .signers([player.keypair])
         .rpc();
     }
@@ -132,12 +212,8 @@ describe("solana-contract", () => {
   it("Fails when the game is full", async () => {
     try {
       await program.methods
-        .joinGame()
-        .accounts({
-          game: gamePDA,
-          player: nonPlayer.publicKey,
-          systemProgram: SystemProgram.programId,
-        })
+        .joinGame(null)
+        .accountsPartial(await joinAccounts(nonPlayer.publicKey))
         .signers([nonPlayer])
         .rpc();
       assert.fail("Should not be able to join a full game.");
@@ -190,12 +266,8 @@ describe("solana-contract", () => {
   it("Fails to join a game that has already started", async () => {
     try {
       await program.methods
-        .joinGame()
-        .accounts({
-          game: gamePDA,
-          player: nonPlayer.publicKey,
-          systemProgram: SystemProgram.programId,
-        })
+        .joinGame(null)
+        .accountsPartial(await joinAccounts(nonPlayer.publicKey))
         .signers([nonPlayer])
         .rpc();
       assert.fail("Should not be able to join a started game.");
@@ -313,9 +385,10 @@ describe("solana-contract", () => {
     const initialBalance = await provider.connection.getBalance(winner.keypair.publicKey);
 
     await program.methods.claimWinnings()
-      .accounts({
+      .accountsPartial({
         game: gamePDA,
         claimer: winner.keypair.publicKey,
+        tokenPot: solPot,
       })
       .signers([winner.keypair])
       .rpc();
@@ -328,9 +401,10 @@ describe("solana-contract", () => {
     const loser = players.find(p => !p.isAlive);
      try {
         await program.methods.claimWinnings()
-        .accounts({
+        .accountsPartial({
             game: gamePDA,
             claimer: loser.keypair.publicKey,
+            tokenPot: solPot,
         })
         .signers([loser.keypair])
         .rpc();
@@ -339,4 +413,79 @@ describe("solana-contract", () => {
         assert.equal(err.error.errorCode.code, "NotWinner");
      }
   });
+
+  it("Refunds a sponsor after rejecting the creator's override", async () => {
+    const counter = await program.account.gameCounter.fetch(counterPDA);
+    const [sponsoredGamePDA] = await PublicKey.findProgramAddress(
+      [Buffer.from("game"), Buffer.from(`game-${counter.count.toNumber() + 1}`)],
+      program.programId
+    );
+    const [sponsoredEscrowPDA] = await PublicKey.findProgramAddress(
+      [Buffer.from("escrow"), sponsoredGamePDA.toBuffer()],
+      program.programId
+    );
+    const [sponsorshipPDA] = await PublicKey.findProgramAddress(
+      [Buffer.from("sponsor"), sponsoredGamePDA.toBuffer(), nonPlayer.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .initializeGame("Sponsored Game", maxPlayers, entryFee, true)
+      .accountsPartial({
+        counter: counterPDA,
+        game: sponsoredGamePDA,
+        config: configPDA,
+        mint: null,
+        escrow: sponsoredEscrowPDA,
+        vault: null,
+        creator: creator.publicKey,
+        tokenProgram: null,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+    await program.methods
+      .sponsorGame(new anchor.BN(LAMPORTS_PER_SOL / 2))
+      .accountsPartial({
+        game: sponsoredGamePDA,
+        sponsorship: sponsorshipPDA,
+        sponsor: nonPlayer.publicKey,
+        tokenPot: solPot,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([nonPlayer])
+      .rpc();
+
+    // With a sponsor's money in the game, the creator can't rewrite it
+    try {
+      await program.methods
+        .updateGameDetails([], [], [], null, new anchor.BN(0), new anchor.BN(0))
+        .accountsPartial({ game: sponsoredGamePDA, authority: creator.publicKey })
+        .rpc();
+      assert.fail("Creator should not be able to override a sponsored game");
+    } catch (err) {
+      assert.equal(err.error.errorCode.code, "OutsideStakes");
+    }
+
+    await program.methods
+      .cancelGame()
+      .accountsPartial({ game: sponsoredGamePDA, creator: creator.publicKey })
+      .rpc();
+
+    const initialBalance = await provider.connection.getBalance(nonPlayer.publicKey);
+    await program.methods
+      .refundSponsorship()
+      .accountsPartial({
+        game: sponsoredGamePDA,
+        sponsorship: sponsorshipPDA,
+        sponsor: nonPlayer.publicKey,
+        tokenPot: solPot,
+      })
+      .signers([nonPlayer])
+      .rpc();
+
+    const finalBalance = await provider.connection.getBalance(nonPlayer.publicKey);
+    assert.isAbove(finalBalance, initialBalance);
+    const sponsorship = await program.account.sponsorship.fetch(sponsorshipPDA);
+    assert.isTrue(sponsorship.refunded);
+  });
 });